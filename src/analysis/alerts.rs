@@ -0,0 +1,370 @@
+use std::fmt;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::analysis::proximity::{ProximityConfig, ProximityDetector, Side, Spotter};
+use crate::mappings::{FiaFlag, Flag, Sector};
+use crate::packets::car_status::CarStatusData;
+use crate::packets::{LapData, MarshalZone};
+use crate::{Telemetry, TelemetryData};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AlertConfig {
+    /// The same alert is not repeated within this many seconds.
+    pub debounce: f32,
+    /// Tyre wear percentage at which the player is told to pit.
+    pub box_tyre_wear: u8,
+    /// Front wing damage percentage at which the player is told to pit.
+    pub box_wing_damage: u8,
+    pub proximity: ProximityConfig,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        AlertConfig {
+            debounce: 3.0,
+            box_tyre_wear: 70,
+            box_wing_damage: 50,
+            proximity: ProximityConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Alert {
+    CarAlongside(Side),
+    Clear(Side),
+    BlueFlag,
+    /// Yellow flag in a marshal zone, with sectors numbered from 1.
+    Yellow {
+        sector: u8,
+    },
+    BoxThisLap,
+    /// Fuel left at the end of the race, in laps, as shown on the MFD.
+    Fuel(f32),
+}
+
+impl Alert {
+    fn same_kind(&self, other: &Alert) -> bool {
+        match (self, other) {
+            (Alert::Fuel(_), Alert::Fuel(_)) => true,
+            _ => self == other,
+        }
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let side = |side: &Side| match side {
+            Side::Left => "left",
+            Side::Right => "right",
+        };
+        match self {
+            Alert::CarAlongside(s) => write!(f, "car {}", side(s)),
+            Alert::Clear(s) => write!(f, "clear {}", side(s)),
+            Alert::BlueFlag => write!(f, "blue flag"),
+            Alert::Yellow { sector } => write!(f, "yellow in sector {}", sector),
+            Alert::BoxThisLap => write!(f, "box this lap"),
+            Alert::Fuel(laps) => write!(f, "fuel {:+.1} laps", laps),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AlertMessage {
+    pub session_time: f32,
+    pub alert: Alert,
+}
+
+impl fmt::Display for AlertMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.alert.fmt(f)
+    }
+}
+
+/// Turns packets into short spotter and race engineer calls for the player,
+/// sent over a channel so speech or overlays can run on another thread.
+#[derive(Debug)]
+pub struct AlertEngine {
+    config: AlertConfig,
+    sender: Sender<AlertMessage>,
+    sent: Vec<AlertMessage>,
+    proximity: ProximityDetector,
+    spotter: Spotter,
+    fia_flag: Option<FiaFlag>,
+    zone_flags: Vec<Flag>,
+    track_length: u16,
+    /// Lap distance at which sectors 2 and 3 start, once the player has
+    /// driven through them.
+    sector_starts: [Option<f32>; 2],
+    lap: Option<LapData>,
+    box_needed: bool,
+    fuel_remaining_laps: Option<f32>,
+}
+
+impl AlertEngine {
+    pub fn new(config: AlertConfig) -> (Self, Receiver<AlertMessage>) {
+        let (sender, receiver) = channel();
+        let engine = AlertEngine {
+            config,
+            sender,
+            sent: Vec::new(),
+            proximity: ProximityDetector::new(config.proximity),
+            spotter: Spotter::default(),
+            fia_flag: None,
+            zone_flags: Vec::new(),
+            track_length: 0,
+            sector_starts: [None; 2],
+            lap: None,
+            box_needed: false,
+            fuel_remaining_laps: None,
+        };
+        (engine, receiver)
+    }
+
+    pub fn update(&mut self, packet: &Telemetry) {
+        let session_time = packet.header.session_time;
+        let player = packet.header.player_car_index as usize;
+
+        match packet.data {
+            TelemetryData::Motion(_) => {
+                self.proximity.update(packet);
+                self.update_spotter(session_time, self.proximity.spotter());
+            }
+            TelemetryData::Session(ref data) => {
+                self.track_length = data.track_length;
+                self.update_zones(session_time, &data.marshal_zones);
+            }
+            TelemetryData::Lap(ref data) => {
+                if let Some(lap) = data.lap_data.get(player) {
+                    self.update_lap(session_time, lap);
+                }
+            }
+            TelemetryData::CarStatus(ref data) => {
+                if let Some(status) = data.car_status_data.get(player) {
+                    self.update_status(session_time, status);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn send(&mut self, session_time: f32, alert: Alert) {
+        let debounce = self.config.debounce;
+        let recent = self.sent.iter().any(|message| {
+            message.alert.same_kind(&alert) && session_time - message.session_time < debounce
+        });
+        if recent {
+            return;
+        }
+
+        let message = AlertMessage {
+            session_time,
+            alert,
+        };
+        self.sent.retain(|sent| {
+            !sent.alert.same_kind(&alert) && session_time - sent.session_time < debounce
+        });
+        self.sent.push(message);
+        // Nobody listening is not an error for the engine.
+        let _ = self.sender.send(message);
+    }
+
+    fn update_spotter(&mut self, session_time: f32, spotter: Spotter) {
+        let previous = std::mem::replace(&mut self.spotter, spotter);
+        for &side in [Side::Left, Side::Right].iter() {
+            match (previous.alongside(side), spotter.alongside(side)) {
+                (None, Some(_)) => self.send(session_time, Alert::CarAlongside(side)),
+                (Some(_), None) => self.send(session_time, Alert::Clear(side)),
+                _ => {}
+            }
+        }
+    }
+
+    fn update_zones(&mut self, session_time: f32, zones: &[MarshalZone]) {
+        self.zone_flags.resize(zones.len(), Flag::None);
+        for (index, zone) in zones.iter().enumerate() {
+            let previous = std::mem::replace(&mut self.zone_flags[index], zone.zone_flag);
+            if zone.zone_flag == Flag::Yellow && previous != Flag::Yellow {
+                let sector = self.sector_of(zone.zone_start);
+                self.send(session_time, Alert::Yellow { sector });
+            }
+        }
+    }
+
+    /// Sector containing a point given as a fraction of the lap. Until the
+    /// player has driven a full lap the sectors are assumed to be equal.
+    fn sector_of(&self, fraction: f32) -> u8 {
+        let track_length = self.track_length as f32;
+        let start = |index: usize| match self.sector_starts[index] {
+            Some(distance) if track_length > 0.0 => distance / track_length,
+            _ => (index + 1) as f32 / 3.0,
+        };
+
+        if fraction >= start(1) {
+            3
+        } else if fraction >= start(0) {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn update_lap(&mut self, session_time: f32, lap: &LapData) {
+        let previous = self.lap.replace(*lap);
+        let previous = match previous {
+            Some(previous) => previous,
+            None => return,
+        };
+
+        if previous.sector != lap.sector {
+            match lap.sector {
+                Sector::Sector2 => self.sector_starts[0] = Some(lap.lap_distance),
+                Sector::Sector3 => self.sector_starts[1] = Some(lap.lap_distance),
+                Sector::Sector1 => {}
+            }
+        }
+
+        if lap.current_lap_num > previous.current_lap_num {
+            if let Some(fuel) = self.fuel_remaining_laps {
+                self.send(session_time, Alert::Fuel(fuel));
+            }
+            if self.box_needed {
+                self.send(session_time, Alert::BoxThisLap);
+            }
+        }
+    }
+
+    fn update_status(&mut self, session_time: f32, status: &CarStatusData) {
+        self.fuel_remaining_laps = Some(status.fuel_remaining_laps);
+
+        let previous = self.fia_flag.replace(status.vehicle_fia_flags);
+        if status.vehicle_fia_flags == FiaFlag::Blue && previous != Some(FiaFlag::Blue) {
+            self.send(session_time, Alert::BlueFlag);
+        }
+
+        let worn = status
+            .tyres_wear
+            .to_array()
+            .iter()
+            .any(|&wear| wear >= self.config.box_tyre_wear);
+        let damaged = status
+            .front_left_wing_damage
+            .max(status.front_right_wing_damage)
+            >= self.config.box_wing_damage;
+        let box_needed = worn || damaged;
+        if box_needed && !self.box_needed {
+            self.send(session_time, Alert::BoxThisLap);
+        }
+        self.box_needed = box_needed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{
+        car_motion, car_status, car_status_packet, compound, lap_data, lap_packet, motion_data,
+        motion_packet, session_data, session_packet,
+    };
+    use crate::mappings::{SessionType, TrackId};
+
+    fn status(fuel_remaining_laps: f32, fia_flag: FiaFlag, wear: u8) -> Vec<CarStatusData> {
+        let mut status = car_status(compound(18));
+        status.fuel_remaining_laps = fuel_remaining_laps;
+        status.vehicle_fia_flags = fia_flag;
+        status.tyres_wear.rear_left = wear;
+        vec![status]
+    }
+
+    fn received(receiver: &Receiver<AlertMessage>) -> Vec<String> {
+        receiver
+            .try_iter()
+            .map(|message| message.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_race_engineer_calls() {
+        let (mut engine, receiver) = AlertEngine::new(AlertConfig::default());
+        engine.update(&car_status_packet(
+            1,
+            0.0,
+            0,
+            status(0.3, FiaFlag::None, 10),
+        ));
+        engine.update(&lap_packet(1, 0.0, 0, vec![lap_data(1, 1, 0.0)]));
+        engine.update(&lap_packet(1, 90.0, 1, vec![lap_data(1, 2, 0.0)]));
+        assert_eq!(received(&receiver), vec!["fuel +0.3 laps"]);
+
+        engine.update(&car_status_packet(
+            1,
+            91.0,
+            2,
+            status(0.3, FiaFlag::Blue, 10),
+        ));
+        engine.update(&car_status_packet(
+            1,
+            91.5,
+            3,
+            status(0.3, FiaFlag::None, 10),
+        ));
+        engine.update(&car_status_packet(
+            1,
+            92.0,
+            4,
+            status(0.3, FiaFlag::Blue, 10),
+        ));
+        engine.update(&car_status_packet(
+            1,
+            99.0,
+            5,
+            status(-0.2, FiaFlag::None, 75),
+        ));
+        engine.update(&lap_packet(1, 180.0, 6, vec![lap_data(1, 3, 0.0)]));
+        assert_eq!(
+            received(&receiver),
+            vec![
+                "blue flag",
+                "box this lap",
+                "fuel -0.2 laps",
+                "box this lap"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_yellow_sector_and_spotter() {
+        let (mut engine, receiver) = AlertEngine::new(AlertConfig::default());
+        let mut session = session_data(SessionType::Race, TrackId::Monza);
+        session.track_length = 3000;
+        session.marshal_zones = vec![
+            MarshalZone {
+                zone_start: 0.1,
+                zone_flag: Flag::Green,
+            },
+            MarshalZone {
+                zone_start: 0.5,
+                zone_flag: Flag::Green,
+            },
+        ];
+        engine.update(&session_packet(1, 0.0, 0, session.clone()));
+
+        let mut lap = lap_data(1, 1, 0.0);
+        engine.update(&lap_packet(1, 0.0, 0, vec![lap]));
+        lap.lap_distance = 1600.0;
+        lap.sector = Sector::Sector2;
+        engine.update(&lap_packet(1, 30.0, 1, vec![lap]));
+
+        session.marshal_zones[1].zone_flag = Flag::Yellow;
+        engine.update(&session_packet(1, 31.0, 2, session.clone()));
+        engine.update(&session_packet(1, 31.5, 3, session));
+
+        let frame = |x| motion_data(vec![car_motion(0.0, 0.0), car_motion(x, 0.0)]);
+        engine.update(&motion_packet(1, 32.0, 4, frame(-3.0)));
+        engine.update(&motion_packet(1, 33.0, 5, frame(-30.0)));
+        assert_eq!(
+            received(&receiver),
+            vec!["yellow in sector 1", "car right", "clear right"]
+        );
+    }
+}
@@ -0,0 +1,338 @@
+use crate::analysis::stats::Summary;
+use crate::mappings::AntiLockBrakes;
+use crate::packets::{CarTelemetryData, MotionData};
+use crate::{Telemetry, TelemetryData, Wheel, WheelData};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BrakingThresholds {
+    /// Brake pedal travel, between 0 and 1, that starts a braking zone.
+    pub brake_on: f32,
+    /// Negative wheel slip ratio beyond which a wheel counts as locked.
+    pub lock_up_slip: f32,
+    /// Cars slower than this, in km/h, are not checked for lock-ups.
+    pub minimum_speed: u16,
+}
+
+impl Default for BrakingThresholds {
+    fn default() -> Self {
+        BrakingThresholds {
+            brake_on: 0.1,
+            lock_up_slip: 0.2,
+            minimum_speed: 30,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LockUp {
+    pub wheel: Wheel,
+    pub session_time: f32,
+    pub lap_distance: f32,
+    pub duration: f32,
+}
+
+/// A stretch of track with the player on the brakes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrakingZone {
+    pub lap: u8,
+    pub start_distance: f32,
+    pub end_distance: f32,
+    pub start_time: f32,
+    pub end_time: f32,
+    /// Speed in km/h when the brakes were applied.
+    pub entry_speed: u16,
+    pub minimum_speed: u16,
+    pub peak_brake: f32,
+    pub front_brake_bias: Option<u8>,
+    /// ABS assist setting when the brakes were applied.
+    pub anti_lock_brakes: Option<AntiLockBrakes>,
+    pub brake_temperature: WheelData<Summary>,
+    pub lock_ups: Vec<LockUp>,
+}
+
+impl BrakingZone {
+    pub fn duration(&self) -> f32 {
+        self.end_time - self.start_time
+    }
+
+    /// The wheels locked at some point in the zone.
+    pub fn is_over_braked(&self) -> bool {
+        !self.lock_ups.is_empty()
+    }
+
+    /// Times a wheel locked again after releasing, with the brakes still
+    /// applied. Slip swinging back and forth across the lock-up threshold is
+    /// ABS releasing and reapplying the brake.
+    pub fn abs_activations(&self) -> usize {
+        Wheel::ALL
+            .iter()
+            .map(|&wheel| {
+                self.lock_ups
+                    .iter()
+                    .filter(|lock_up| lock_up.wheel == wheel)
+                    .count()
+                    .saturating_sub(1)
+            })
+            .sum()
+    }
+}
+
+/// Slip of a wheel under braking: the reported slip ratio, or the shortfall
+/// of the wheel's speed against `car_speed` in metres per second if that is
+/// further from rolling freely.
+pub(crate) fn braking_slip(data: &MotionData, wheel: Wheel, car_speed: f32) -> f32 {
+    let slip = *data.wheel_slip.get(wheel);
+    if car_speed > 0.0 {
+        slip.min(data.wheel_speed.get(wheel).abs() / car_speed - 1.0)
+    } else {
+        slip
+    }
+}
+
+/// Splits the player's laps into braking zones.
+#[derive(Debug, Default)]
+pub struct BrakeAnalyzer {
+    thresholds: BrakingThresholds,
+    lap: u8,
+    lap_distance: f32,
+    speed: u16,
+    front_brake_bias: Option<u8>,
+    anti_lock_brakes: Option<AntiLockBrakes>,
+    locked: WheelData<Option<(f32, f32)>>,
+    current: Option<BrakingZone>,
+    zones: Vec<BrakingZone>,
+}
+
+impl BrakeAnalyzer {
+    pub fn new(thresholds: BrakingThresholds) -> Self {
+        BrakeAnalyzer {
+            thresholds,
+            ..BrakeAnalyzer::default()
+        }
+    }
+
+    pub fn zones(&self) -> &[BrakingZone] {
+        &self.zones
+    }
+
+    /// Returns the braking zone completed by this packet, if any.
+    pub fn update(&mut self, packet: &Telemetry) -> Option<&BrakingZone> {
+        let player = packet.header.player_car_index as usize;
+        let session_time = packet.header.session_time;
+
+        match packet.data {
+            TelemetryData::Lap(ref data) => {
+                if let Some(lap) = data.lap_data.get(player) {
+                    self.lap = lap.current_lap_num;
+                    self.lap_distance = lap.lap_distance;
+                }
+            }
+            TelemetryData::CarStatus(ref data) => {
+                if let Some(status) = data.car_status_data.get(player) {
+                    self.front_brake_bias = Some(status.front_brake_bias);
+                    self.anti_lock_brakes = Some(status.anti_lock_brakes);
+                }
+            }
+            TelemetryData::Motion(ref data) => self.update_motion(session_time, data),
+            TelemetryData::CarTelemetry(ref data) => {
+                if let Some(telemetry) = data.car_telemetry_data.get(player) {
+                    if self.update_telemetry(session_time, telemetry) {
+                        return self.zones.last();
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    /// Returns true when a braking zone has just finished.
+    fn update_telemetry(&mut self, session_time: f32, telemetry: &CarTelemetryData) -> bool {
+        self.speed = telemetry.speed;
+        let braking = telemetry.brake >= self.thresholds.brake_on;
+
+        if braking {
+            let (lap, lap_distance) = (self.lap, self.lap_distance);
+            let (front_brake_bias, anti_lock_brakes) =
+                (self.front_brake_bias, self.anti_lock_brakes);
+            let zone = self.current.get_or_insert_with(|| BrakingZone {
+                lap,
+                start_distance: lap_distance,
+                end_distance: lap_distance,
+                start_time: session_time,
+                end_time: session_time,
+                entry_speed: telemetry.speed,
+                minimum_speed: telemetry.speed,
+                peak_brake: 0.0,
+                front_brake_bias,
+                anti_lock_brakes,
+                brake_temperature: WheelData::default(),
+                lock_ups: Vec::new(),
+            });
+            zone.end_distance = lap_distance;
+            zone.end_time = session_time;
+            zone.minimum_speed = zone.minimum_speed.min(telemetry.speed);
+            zone.peak_brake = zone.peak_brake.max(telemetry.brake);
+            for &wheel in Wheel::ALL.iter() {
+                zone.brake_temperature
+                    .get_mut(wheel)
+                    .add(*telemetry.brakes_temperature.get(wheel) as f32);
+            }
+            return false;
+        }
+
+        match self.current.take() {
+            Some(mut zone) => {
+                zone.end_time = session_time;
+                for &wheel in Wheel::ALL.iter() {
+                    if let Some(lock_up) = self.release(wheel, session_time) {
+                        zone.lock_ups.push(lock_up);
+                    }
+                }
+                self.zones.push(zone);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn update_motion(&mut self, session_time: f32, data: &MotionData) {
+        let zone_open = self.current.is_some();
+        let car_speed = self.speed as f32 / 3.6;
+
+        for &wheel in Wheel::ALL.iter() {
+            let locked = zone_open
+                && self.speed >= self.thresholds.minimum_speed
+                && braking_slip(data, wheel, car_speed) <= -self.thresholds.lock_up_slip;
+
+            if locked {
+                if self.locked.get(wheel).is_none() {
+                    *self.locked.get_mut(wheel) = Some((session_time, self.lap_distance));
+                }
+            } else if let Some(lock_up) = self.release(wheel, session_time) {
+                if let Some(zone) = self.current.as_mut() {
+                    zone.lock_ups.push(lock_up);
+                }
+            }
+        }
+    }
+
+    fn release(&mut self, wheel: Wheel, session_time: f32) -> Option<LockUp> {
+        self.locked
+            .get_mut(wheel)
+            .take()
+            .map(|(start, lap_distance)| LockUp {
+                wheel,
+                session_time: start,
+                lap_distance,
+                duration: session_time - start,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{
+        car_motion, car_telemetry, car_telemetry_packet, lap_data, lap_packet, motion_data,
+        motion_packet, wheels,
+    };
+
+    #[test]
+    fn test_braking_zone_with_lock_up() {
+        let mut analyzer = BrakeAnalyzer::default();
+        let mut lap = lap_data(1, 2, 1000.0);
+        analyzer.update(&lap_packet(1, 0.0, 0, vec![lap]));
+        assert!(analyzer
+            .update(&car_telemetry_packet(
+                1,
+                0.0,
+                0,
+                vec![car_telemetry(300, 1.0, 0.0)]
+            ))
+            .is_none());
+
+        let mut telemetry = car_telemetry(290, 0.0, 1.0);
+        telemetry.brakes_temperature.front_left = 700;
+        analyzer.update(&car_telemetry_packet(1, 0.1, 1, vec![telemetry]));
+
+        let mut motion = motion_data(vec![car_motion(0.0, 0.0)]);
+        motion.wheel_speed = wheels(80.0);
+        motion.wheel_slip = wheels(0.0);
+        motion.wheel_speed.front_right = 10.0;
+        motion.wheel_slip.front_right = -0.9;
+        analyzer.update(&motion_packet(1, 0.2, 2, motion.clone()));
+
+        lap.lap_distance = 1100.0;
+        analyzer.update(&lap_packet(1, 0.3, 3, vec![lap]));
+        analyzer.update(&car_telemetry_packet(
+            1,
+            0.3,
+            3,
+            vec![car_telemetry(150, 0.0, 0.8)],
+        ));
+
+        motion.wheel_speed.front_right = 42.0;
+        motion.wheel_slip.front_right = 0.0;
+        analyzer.update(&motion_packet(1, 0.5, 4, motion));
+
+        let zone = analyzer
+            .update(&car_telemetry_packet(
+                1,
+                0.6,
+                5,
+                vec![car_telemetry(140, 0.5, 0.0)],
+            ))
+            .cloned()
+            .unwrap();
+        assert_eq!(zone.lap, 2);
+        assert_eq!(zone.start_distance, 1000.0);
+        assert_eq!(zone.end_distance, 1100.0);
+        assert_eq!(zone.entry_speed, 290);
+        assert_eq!(zone.minimum_speed, 150);
+        assert_eq!(zone.peak_brake, 1.0);
+        assert_eq!(zone.brake_temperature.front_left.max, 700.0);
+        assert!((zone.duration() - 0.5).abs() < 1e-6);
+
+        assert!(zone.is_over_braked());
+        assert_eq!(zone.lock_ups.len(), 1);
+        assert_eq!(zone.lock_ups[0].wheel, Wheel::FrontRight);
+        assert!((zone.lock_ups[0].duration - 0.3).abs() < 1e-6);
+        assert_eq!(zone.abs_activations(), 0);
+        assert_eq!(analyzer.zones().len(), 1);
+    }
+
+    #[test]
+    fn test_abs_activations() {
+        let mut analyzer = BrakeAnalyzer::default();
+        let mut motion = motion_data(vec![car_motion(0.0, 0.0)]);
+        motion.wheel_speed = wheels(60.0);
+        motion.wheel_slip = wheels(0.0);
+
+        // The front left slip swings across the threshold three times.
+        for (tick, slip) in [-0.25, -0.15, -0.25, -0.1, -0.3, 0.0].iter().enumerate() {
+            let time = tick as f32 * 0.1;
+            analyzer.update(&car_telemetry_packet(
+                1,
+                time,
+                0,
+                vec![car_telemetry(220, 0.0, 1.0)],
+            ));
+            motion.wheel_slip.front_left = *slip;
+            analyzer.update(&motion_packet(1, time, 0, motion.clone()));
+        }
+
+        let zone = analyzer
+            .update(&car_telemetry_packet(
+                1,
+                0.6,
+                0,
+                vec![car_telemetry(120, 1.0, 0.0)],
+            ))
+            .cloned()
+            .unwrap();
+        assert_eq!(zone.lock_ups.len(), 3);
+        assert_eq!(zone.abs_activations(), 2);
+    }
+}
@@ -0,0 +1,419 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{BufRead, Write};
+
+use crate::analysis::classification::Classification;
+use crate::error::LoadError;
+use crate::mappings::{ResultStatus, TeamId};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointsSystem {
+    /// Points awarded for each finishing position, starting with the winner.
+    pub positions: Vec<u32>,
+    /// Bonus for setting the fastest lap of the race.
+    pub fastest_lap: u32,
+    /// The fastest lap bonus is only awarded to drivers classified in this
+    /// position or higher. `None` awards it regardless of position.
+    pub fastest_lap_cutoff: Option<u8>,
+}
+
+impl PointsSystem {
+    /// The 2019 Formula 1 system, with a point for fastest lap inside the top ten.
+    pub fn formula1() -> Self {
+        PointsSystem {
+            positions: vec![25, 18, 15, 12, 10, 8, 6, 4, 2, 1],
+            fastest_lap: 1,
+            fastest_lap_cutoff: Some(10),
+        }
+    }
+
+    fn points(&self, entry: &RaceEntry) -> u32 {
+        if !entry.is_classified() {
+            return 0;
+        }
+
+        let mut points = self
+            .positions
+            .get(entry.position as usize - 1)
+            .copied()
+            .unwrap_or(0);
+        if entry.fastest_lap {
+            match self.fastest_lap_cutoff {
+                Some(cutoff) if entry.position > cutoff => {}
+                _ => points += self.fastest_lap,
+            }
+        }
+        points
+    }
+}
+
+impl Default for PointsSystem {
+    fn default() -> Self {
+        PointsSystem::formula1()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RaceEntry {
+    pub name: String,
+    pub team_id: TeamId,
+    pub position: u8,
+    pub result_status: ResultStatus,
+    pub fastest_lap: bool,
+}
+
+impl RaceEntry {
+    fn is_classified(&self) -> bool {
+        match self.result_status {
+            ResultStatus::Finished | ResultStatus::Active => self.position > 0,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RaceResult {
+    pub session_uid: u64,
+    pub entries: Vec<RaceEntry>,
+}
+
+impl From<&Classification> for RaceResult {
+    fn from(classification: &Classification) -> Self {
+        let fastest = classification
+            .fastest_lap
+            .map(|(vehicle_index, _)| vehicle_index);
+        RaceResult {
+            session_uid: classification.session_uid,
+            entries: classification
+                .entries
+                .iter()
+                .filter_map(|entry| {
+                    entry.team_id.map(|team_id| RaceEntry {
+                        name: entry.name.clone(),
+                        team_id,
+                        position: entry.position,
+                        result_status: entry.result_status,
+                        fastest_lap: fastest == Some(entry.vehicle_index),
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriverStanding {
+    pub name: String,
+    /// The team the driver most recently raced for.
+    pub team_id: TeamId,
+    pub points: u32,
+    /// Number of results in each position, used for countback.
+    pub finishes: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstructorStanding {
+    pub team_id: TeamId,
+    pub points: u32,
+    pub finishes: Vec<u32>,
+}
+
+/// Orders by points, then by countback of best finishes.
+fn compare(points: (u32, &[u32]), other: (u32, &[u32])) -> Ordering {
+    other.0.cmp(&points.0).then_with(|| {
+        let length = points.1.len().max(other.1.len());
+        (0..length)
+            .map(|position| {
+                let count = points.1.get(position).copied().unwrap_or(0);
+                let other_count = other.1.get(position).copied().unwrap_or(0);
+                other_count.cmp(&count)
+            })
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    })
+}
+
+fn add_finish(finishes: &mut Vec<u32>, position: u8) {
+    let index = position as usize - 1;
+    if finishes.len() <= index {
+        finishes.resize(index + 1, 0);
+    }
+    finishes[index] += 1;
+}
+
+/// Driver and constructor standings over a season of races.
+#[derive(Debug, Default)]
+pub struct Championship {
+    points: PointsSystem,
+    races: Vec<RaceResult>,
+}
+
+impl Championship {
+    pub fn new(points: PointsSystem) -> Self {
+        Championship {
+            points,
+            races: Vec::new(),
+        }
+    }
+
+    pub fn races(&self) -> &[RaceResult] {
+        &self.races
+    }
+
+    /// Adds a race, replacing any earlier result for the same session.
+    pub fn add_race<R: Into<RaceResult>>(&mut self, result: R) {
+        let result = result.into();
+        match self
+            .races
+            .iter_mut()
+            .find(|race| race.session_uid == result.session_uid)
+        {
+            Some(race) => *race = result,
+            None => self.races.push(result),
+        }
+    }
+
+    pub fn driver_standings(&self) -> Vec<DriverStanding> {
+        let mut standings: Vec<DriverStanding> = Vec::new();
+        let mut indexes: HashMap<&str, usize> = HashMap::new();
+
+        for entry in self.races.iter().flat_map(|race| race.entries.iter()) {
+            let index = *indexes.entry(&entry.name).or_insert_with(|| {
+                standings.push(DriverStanding {
+                    name: entry.name.clone(),
+                    team_id: entry.team_id,
+                    points: 0,
+                    finishes: Vec::new(),
+                });
+                standings.len() - 1
+            });
+
+            let standing = &mut standings[index];
+            standing.team_id = entry.team_id;
+            standing.points += self.points.points(entry);
+            if entry.is_classified() {
+                add_finish(&mut standing.finishes, entry.position);
+            }
+        }
+
+        standings.sort_by(|a, b| {
+            compare((a.points, &a.finishes), (b.points, &b.finishes))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        standings
+    }
+
+    pub fn constructor_standings(&self) -> Vec<ConstructorStanding> {
+        let mut standings: Vec<ConstructorStanding> = Vec::new();
+
+        for entry in self.races.iter().flat_map(|race| race.entries.iter()) {
+            let index = match standings
+                .iter()
+                .position(|standing| standing.team_id == entry.team_id)
+            {
+                Some(index) => index,
+                None => {
+                    standings.push(ConstructorStanding {
+                        team_id: entry.team_id,
+                        points: 0,
+                        finishes: Vec::new(),
+                    });
+                    standings.len() - 1
+                }
+            };
+
+            let standing = &mut standings[index];
+            standing.points += self.points.points(entry);
+            if entry.is_classified() {
+                add_finish(&mut standing.finishes, entry.position);
+            }
+        }
+
+        standings.sort_by(|a, b| {
+            compare((a.points, &a.finishes), (b.points, &b.finishes))
+                .then_with(|| (a.team_id as u8).cmp(&(b.team_id as u8)))
+        });
+        standings
+    }
+
+    /// Writes every race result so the season can be resumed with `load`.
+    ///
+    /// Each race starts with a `race <session uid>` line followed by one tab
+    /// separated line per driver.
+    pub fn save<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        for race in &self.races {
+            writeln!(writer, "race\t{}", race.session_uid)?;
+            for entry in &race.entries {
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}\t{}",
+                    entry.position,
+                    entry.result_status as u8,
+                    entry.team_id as u8,
+                    entry.fastest_lap as u8,
+                    entry.name
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load<R: BufRead>(reader: R, points: PointsSystem) -> Result<Self, LoadError> {
+        let mut championship = Championship::new(points);
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let number = index + 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.splitn(5, '\t').collect();
+            if let ["race", session_uid] = fields[..] {
+                let session_uid = session_uid
+                    .parse()
+                    .map_err(|_| LoadError::malformed(number))?;
+                championship.races.push(RaceResult {
+                    session_uid,
+                    entries: Vec::new(),
+                });
+                continue;
+            }
+
+            let entry = match fields[..] {
+                [position, result_status, team_id, fastest_lap, name] => {
+                    parse_entry(position, result_status, team_id, fastest_lap, name)
+                }
+                _ => None,
+            };
+            match (championship.races.last_mut(), entry) {
+                (Some(race), Some(entry)) => race.entries.push(entry),
+                _ => return Err(LoadError::malformed(number)),
+            }
+        }
+
+        Ok(championship)
+    }
+}
+
+fn parse_entry(
+    position: &str,
+    result_status: &str,
+    team_id: &str,
+    fastest_lap: &str,
+    name: &str,
+) -> Option<RaceEntry> {
+    Some(RaceEntry {
+        name: name.to_owned(),
+        team_id: TeamId::try_from(team_id.parse::<u8>().ok()?).ok()?,
+        position: position.parse().ok()?,
+        result_status: ResultStatus::try_from(result_status.parse::<u8>().ok()?).ok()?,
+        fastest_lap: fastest_lap == "1",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, team_id: TeamId, position: u8, fastest_lap: bool) -> RaceEntry {
+        RaceEntry {
+            name: name.to_owned(),
+            team_id,
+            position,
+            result_status: ResultStatus::Finished,
+            fastest_lap,
+        }
+    }
+
+    fn season() -> Championship {
+        let mut championship = Championship::new(PointsSystem::formula1());
+        championship.add_race(RaceResult {
+            session_uid: 1,
+            entries: vec![
+                entry("HAMILTON", TeamId::Mercedes, 1, false),
+                entry("VETTEL", TeamId::Ferrari, 2, true),
+                entry("BOTTAS", TeamId::Mercedes, 3, false),
+            ],
+        });
+        championship.add_race(RaceResult {
+            session_uid: 2,
+            entries: vec![
+                entry("VETTEL", TeamId::Ferrari, 1, false),
+                entry("HAMILTON", TeamId::Mercedes, 2, true),
+                RaceEntry {
+                    result_status: ResultStatus::Retired,
+                    ..entry("BOTTAS", TeamId::Mercedes, 3, false)
+                },
+            ],
+        });
+        championship
+    }
+
+    #[test]
+    fn test_driver_standings() {
+        let standings = season().driver_standings();
+        let summary: Vec<(&str, u32)> = standings
+            .iter()
+            .map(|standing| (standing.name.as_str(), standing.points))
+            .collect();
+
+        // Hamilton and Vettel are tied on points and wins, and the countback
+        // falls back to the alphabetical order.
+        assert_eq!(
+            summary,
+            vec![("HAMILTON", 44), ("VETTEL", 44), ("BOTTAS", 15)]
+        );
+    }
+
+    #[test]
+    fn test_constructor_standings() {
+        let standings = season().constructor_standings();
+        assert_eq!(standings[0].team_id, TeamId::Mercedes);
+        assert_eq!(standings[0].points, 59);
+        assert_eq!(standings[1].team_id, TeamId::Ferrari);
+        assert_eq!(standings[1].points, 44);
+    }
+
+    #[test]
+    fn test_countback() {
+        let mut championship = Championship::new(PointsSystem {
+            positions: vec![2, 2, 1],
+            fastest_lap: 0,
+            fastest_lap_cutoff: None,
+        });
+        championship.add_race(RaceResult {
+            session_uid: 1,
+            entries: vec![
+                entry("B", TeamId::Williams, 1, false),
+                entry("A", TeamId::Haas, 3, false),
+            ],
+        });
+        championship.add_race(RaceResult {
+            session_uid: 2,
+            entries: vec![
+                entry("A", TeamId::Haas, 2, false),
+                entry("B", TeamId::Williams, 3, false),
+            ],
+        });
+
+        let standings = championship.driver_standings();
+        assert_eq!(standings[0].points, standings[1].points);
+        assert_eq!(standings[0].name, "B");
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let championship = season();
+        let mut saved = Vec::new();
+        championship.save(&mut saved).unwrap();
+
+        let loaded = Championship::load(&saved[..], PointsSystem::formula1()).unwrap();
+        assert_eq!(loaded.races(), championship.races());
+        assert_eq!(loaded.driver_standings(), championship.driver_standings());
+
+        let error = Championship::load(&b"race\t1\nnot a result\n"[..], PointsSystem::default());
+        assert_eq!(error.unwrap_err().to_string(), "Malformed data on line 2");
+    }
+}
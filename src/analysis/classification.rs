@@ -0,0 +1,456 @@
+use std::fmt::Write;
+
+use crate::analysis::event_log::Driver;
+use crate::mappings::{ResultStatus, TeamId};
+use crate::packets::{EventDataDetails, LapData};
+use crate::{Telemetry, TelemetryData};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Gap {
+    Leader,
+    Time(f32),
+    Laps(u8),
+    /// A line crossing of either car was never seen.
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassifiedDriver {
+    pub position: u8,
+    pub vehicle_index: u8,
+    pub name: String,
+    pub team_id: Option<TeamId>,
+    pub race_number: Option<u8>,
+    pub grid_position: u8,
+    pub laps_completed: u8,
+    /// Session time from the start to the car's last crossing of the line,
+    /// excluding penalties. `None` when the start or crossing was not seen.
+    pub race_time: Option<f32>,
+    pub penalties: u8,
+    pub best_lap_time: Option<f32>,
+    pub result_status: ResultStatus,
+    pub gap: Gap,
+}
+
+impl ClassifiedDriver {
+    /// Positions gained from the grid; negative when positions were lost.
+    pub fn positions_gained(&self) -> i16 {
+        self.grid_position as i16 - self.position as i16
+    }
+
+    /// Short reason for a car not being classified as a finisher.
+    pub fn reason(&self) -> Option<&'static str> {
+        match self.result_status {
+            ResultStatus::Retired => Some("DNF"),
+            ResultStatus::Disqualified => Some("DSQ"),
+            ResultStatus::NotClassified => Some("NC"),
+            _ => None,
+        }
+    }
+
+    fn gap_text(&self) -> String {
+        match self.reason() {
+            Some(reason) => reason.to_owned(),
+            None => match self.gap {
+                Gap::Leader | Gap::Unknown => String::new(),
+                Gap::Time(seconds) => format!("+{:.3}", seconds),
+                Gap::Laps(1) => "+1 lap".to_owned(),
+                Gap::Laps(laps) => format!("+{} laps", laps),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Classification {
+    pub session_uid: u64,
+    pub entries: Vec<ClassifiedDriver>,
+    /// Vehicle index and time of the fastest lap of the session.
+    pub fastest_lap: Option<(u8, f32)>,
+}
+
+impl Classification {
+    pub fn to_csv(&self) -> String {
+        let mut output = String::from(
+            "position,race_number,name,team,grid,positions_gained,laps,race_time,penalties,best_lap,gap,status\n",
+        );
+        for entry in &self.entries {
+            writeln!(
+                output,
+                "{},{},{},{},{},{},{},{},{},{},{},{:?}",
+                entry.position,
+                optional(entry.race_number),
+                csv_escape(&entry.name),
+                entry
+                    .team_id
+                    .map(|team_id| format!("{:?}", team_id))
+                    .unwrap_or_default(),
+                entry.grid_position,
+                entry.positions_gained(),
+                entry.laps_completed,
+                entry
+                    .race_time
+                    .map(|time| format!("{:.3}", time))
+                    .unwrap_or_default(),
+                entry.penalties,
+                entry
+                    .best_lap_time
+                    .map(|time| format!("{:.3}", time))
+                    .unwrap_or_default(),
+                entry.gap_text(),
+                entry.result_status,
+            )
+            .unwrap();
+        }
+        output
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut output = format!("{{\"session_uid\":{},\"results\":[", self.session_uid);
+        for (index, entry) in self.entries.iter().enumerate() {
+            if index > 0 {
+                output.push(',');
+            }
+            write!(
+                output,
+                "{{\"position\":{},\"race_number\":{},\"name\":\"{}\",\"team\":{},\
+                 \"grid\":{},\"positions_gained\":{},\"laps\":{},\"race_time\":{},\
+                 \"penalties\":{},\"best_lap\":{},\"gap\":\"{}\",\"status\":\"{:?}\"}}",
+                entry.position,
+                entry
+                    .race_number
+                    .map(|number| number.to_string())
+                    .unwrap_or_else(|| "null".to_owned()),
+                json_escape(&entry.name),
+                entry
+                    .team_id
+                    .map(|team_id| format!("\"{:?}\"", team_id))
+                    .unwrap_or_else(|| "null".to_owned()),
+                entry.grid_position,
+                entry.positions_gained(),
+                entry.laps_completed,
+                entry
+                    .race_time
+                    .map(|time| format!("{:.3}", time))
+                    .unwrap_or_else(|| "null".to_owned()),
+                entry.penalties,
+                entry
+                    .best_lap_time
+                    .map(|time| format!("{:.3}", time))
+                    .unwrap_or_else(|| "null".to_owned()),
+                json_escape(&entry.gap_text()),
+                entry.result_status,
+            )
+            .unwrap();
+        }
+        output.push_str("]}");
+        output
+    }
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+fn json_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reconstructs the final classification of a race, which the game never
+/// sends, from lap and participant data.
+#[derive(Debug, Default)]
+pub struct ClassificationBuilder {
+    session_uid: u64,
+    drivers: Vec<Driver>,
+    laps: Vec<LapData>,
+    /// Session time the race started, from a car seen on its first lap.
+    start_time: Option<f32>,
+    /// Session time of each car's latest crossing of the line.
+    crossings: Vec<Option<f32>>,
+    fastest_lap: Option<(u8, f32)>,
+    classification: Option<Classification>,
+}
+
+impl ClassificationBuilder {
+    pub fn new() -> Self {
+        ClassificationBuilder::default()
+    }
+
+    /// The classification built at the latest `CHQF` or `SEND` event.
+    pub fn classification(&self) -> Option<&Classification> {
+        self.classification.as_ref()
+    }
+
+    /// Returns the classification when the packet finalised one.
+    pub fn update(&mut self, packet: &Telemetry) -> Option<&Classification> {
+        if packet.header.session_uid != self.session_uid {
+            *self = ClassificationBuilder {
+                session_uid: packet.header.session_uid,
+                ..ClassificationBuilder::default()
+            };
+        }
+
+        match packet.data {
+            TelemetryData::Participants(ref data) => {
+                self.drivers = data.participants.iter().map(Driver::from).collect();
+            }
+            TelemetryData::Lap(ref data) => {
+                self.update_laps(packet.header.session_time, &data.lap_data)
+            }
+            TelemetryData::Event(ref event) => match event.event_details {
+                EventDataDetails::FastestLap {
+                    vehicle_index,
+                    lap_time,
+                } => self.fastest_lap = Some((vehicle_index, lap_time)),
+                EventDataDetails::ChequeredFlag | EventDataDetails::SessionEnded => {
+                    self.classification = Some(self.build());
+                    return self.classification.as_ref();
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+
+        None
+    }
+
+    fn update_laps(&mut self, session_time: f32, laps: &[LapData]) {
+        self.crossings.resize(laps.len(), None);
+        for (index, current) in laps.iter().enumerate() {
+            if current.current_lap_num == 1 && current.result_status == ResultStatus::Active {
+                let start = session_time - current.current_lap_time;
+                if self.start_time.is_none() || Some(start) < self.start_time {
+                    self.start_time = Some(start);
+                }
+            }
+
+            let previous = match self.laps.get(index) {
+                Some(previous) => previous,
+                None => continue,
+            };
+            if current.current_lap_num > previous.current_lap_num {
+                self.crossings[index] = Some(session_time - current.current_lap_time);
+            } else if current.result_status == ResultStatus::Finished
+                && previous.result_status != ResultStatus::Finished
+            {
+                // The game does not start a new lap for the finish.
+                self.crossings[index] = Some(session_time);
+            }
+        }
+        self.laps = laps.to_vec();
+    }
+
+    /// Builds the classification from the latest known state.
+    pub fn build(&self) -> Classification {
+        let mut entries: Vec<ClassifiedDriver> = self
+            .laps
+            .iter()
+            .enumerate()
+            .filter(|(_, lap)| match lap.result_status {
+                ResultStatus::Invalid | ResultStatus::Inactive => false,
+                _ => lap.car_position > 0,
+            })
+            .map(|(index, lap)| {
+                let driver = self.drivers.get(index);
+                let laps_completed = match lap.result_status {
+                    ResultStatus::Finished => lap.current_lap_num,
+                    _ => lap.current_lap_num.saturating_sub(1),
+                };
+                ClassifiedDriver {
+                    position: lap.car_position,
+                    vehicle_index: index as u8,
+                    name: driver
+                        .map(|driver| driver.name.clone())
+                        .unwrap_or_else(|| format!("Car {}", index)),
+                    team_id: driver.map(|driver| driver.team_id),
+                    race_number: driver.map(|driver| driver.race_number),
+                    grid_position: lap.grid_position,
+                    laps_completed,
+                    race_time: self.crossings[index]
+                        .and_then(|crossing| Some(crossing - self.start_time?)),
+                    penalties: lap.penalties,
+                    best_lap_time: if lap.best_lap_time > 0.0 {
+                        Some(lap.best_lap_time)
+                    } else {
+                        None
+                    },
+                    result_status: lap.result_status,
+                    gap: Gap::Leader,
+                }
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry.position);
+
+        // Gaps come from the crossings alone, so they hold without the start.
+        let finish = |entry: &ClassifiedDriver| {
+            Some(self.crossings[entry.vehicle_index as usize]? + entry.penalties as f32)
+        };
+        if let Some(leader) = entries.first().cloned() {
+            for entry in entries.iter_mut().skip(1) {
+                entry.gap = if entry.laps_completed < leader.laps_completed {
+                    Gap::Laps(leader.laps_completed - entry.laps_completed)
+                } else {
+                    match (finish(entry), finish(&leader)) {
+                        (Some(time), Some(leader_time)) => Gap::Time(time - leader_time),
+                        _ => Gap::Unknown,
+                    }
+                };
+            }
+        }
+
+        Classification {
+            session_uid: self.session_uid,
+            entries,
+            fastest_lap: self.fastest_lap,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{
+        event_packet, lap_data, lap_packet, participant, participants_packet,
+    };
+
+    fn finished(position: u8, grid_position: u8, best_lap_time: f32) -> LapData {
+        let mut lap = lap_data(position, 2, 10000.0);
+        lap.grid_position = grid_position;
+        lap.best_lap_time = best_lap_time;
+        lap.result_status = ResultStatus::Finished;
+        lap
+    }
+
+    #[test]
+    fn test_build_classification() {
+        let mut builder = ClassificationBuilder::new();
+        builder.update(&participants_packet(
+            7,
+            vec![
+                participant("HAMILTON", TeamId::Mercedes, 44),
+                participant("LECLERC", TeamId::Ferrari, 16),
+                participant("O\"WARD, P", TeamId::Williams, 6),
+            ],
+        ));
+
+        let lap = |position, lap, current_lap_time| {
+            let mut data = lap_data(position, lap, 0.0);
+            data.current_lap_time = current_lap_time;
+            data
+        };
+        builder.update(&lap_packet(
+            7,
+            0.5,
+            1,
+            vec![lap(2, 1, 0.5), lap(1, 1, 0.5), lap(3, 1, 0.5)],
+        ));
+        builder.update(&lap_packet(
+            7,
+            90.5,
+            2,
+            vec![lap(1, 2, 0.5), lap(2, 2, 0.0), lap(3, 2, 0.2)],
+        ));
+
+        // The leader takes the flag a second before the second car.
+        let mut retired = lap_data(3, 2, 9000.0);
+        retired.result_status = ResultStatus::Retired;
+        builder.update(&lap_packet(
+            7,
+            181.0,
+            3,
+            vec![finished(1, 2, 90.0), lap(2, 2, 91.0), retired],
+        ));
+        builder.update(&lap_packet(
+            7,
+            182.0,
+            4,
+            vec![finished(1, 2, 90.0), finished(2, 1, 91.0), retired],
+        ));
+
+        builder.update(&event_packet(
+            7,
+            150.0,
+            5,
+            "FTLP",
+            EventDataDetails::FastestLap {
+                vehicle_index: 0,
+                lap_time: 90.0,
+            },
+        ));
+        assert!(builder
+            .update(&event_packet(
+                7,
+                182.5,
+                6,
+                "CHQF",
+                EventDataDetails::ChequeredFlag
+            ))
+            .is_some());
+
+        let classification = builder.classification().unwrap();
+        let entries = &classification.entries;
+        assert_eq!(classification.fastest_lap, Some((0, 90.0)));
+        assert_eq!(entries[0].name, "HAMILTON");
+        assert_eq!(entries[0].race_time, Some(181.0));
+        assert_eq!(entries[0].positions_gained(), 1);
+        assert_eq!(entries[1].gap, Gap::Time(1.0));
+        assert_eq!(entries[1].positions_gained(), -1);
+        assert_eq!(entries[2].reason(), Some("DNF"));
+        assert_eq!(entries[2].gap, Gap::Laps(1));
+
+        assert_eq!(
+            classification.to_csv(),
+            "position,race_number,name,team,grid,positions_gained,laps,race_time,penalties,best_lap,gap,status\n\
+             1,44,HAMILTON,Mercedes,2,1,2,181.000,0,90.000,,Finished\n\
+             2,16,LECLERC,Ferrari,1,-1,2,182.000,0,91.000,+1.000,Finished\n\
+             3,6,\"O\"\"WARD, P\",Williams,3,0,1,90.300,0,,DNF,Retired\n"
+        );
+        assert!(classification.to_json().starts_with(
+            "{\"session_uid\":7,\"results\":[{\"position\":1,\"race_number\":44,\"name\":\"HAMILTON\""
+        ));
+        assert!(classification
+            .to_json()
+            .contains("\"name\":\"O\\\"WARD, P\",\"team\":\"Williams\""));
+    }
+
+    #[test]
+    fn test_joined_mid_race() {
+        let mut builder = ClassificationBuilder::new();
+        builder.update(&lap_packet(1, 10.0, 0, vec![lap_data(1, 1, 0.0)]));
+        builder.update(&lap_packet(
+            2,
+            500.0,
+            0,
+            vec![lap_data(1, 5, 0.0), lap_data(2, 5, 0.0)],
+        ));
+        builder.update(&lap_packet(
+            2,
+            590.0,
+            1,
+            vec![lap_data(1, 6, 0.0), lap_data(2, 5, 0.0)],
+        ));
+
+        // The new session has no start time and the second car no crossing.
+        let classification = builder.build();
+        assert_eq!(classification.session_uid, 2);
+        assert_eq!(classification.entries[0].race_time, None);
+        assert_eq!(classification.entries[1].gap, Gap::Laps(1));
+    }
+}
@@ -0,0 +1,557 @@
+use std::collections::HashMap;
+
+use crate::mappings::TrackId;
+use crate::{Telemetry, TelemetryData};
+
+/// How close to the start and finish line, in metres, the player's trace
+/// must begin and end to count as a full lap.
+const LAP_END_TOLERANCE: f32 = 100.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CornerConfig {
+    /// Lateral acceleration, in g, from which the car counts as cornering.
+    pub lateral_g: f32,
+    /// Steering input, between 0 and 1, from which the car counts as
+    /// cornering.
+    pub steer: f32,
+    /// Turns in the same direction closer than this, in metres, are one
+    /// corner.
+    pub merge_gap: f32,
+    /// Shorter turns, in metres, are ignored.
+    pub minimum_length: f32,
+}
+
+impl Default for CornerConfig {
+    fn default() -> Self {
+        CornerConfig {
+            lateral_g: 1.0,
+            steer: 0.15,
+            merge_gap: 40.0,
+            minimum_length: 20.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Direction {
+    Left,
+    Right,
+}
+
+/// A point on a lap used to find the corners.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CornerSample {
+    pub lap_distance: f32,
+    /// Lateral acceleration, negative to the left.
+    pub lateral_g: f32,
+    /// Steering input, negative to the left.
+    pub steer: f32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Corner {
+    /// Counted from 1 in the order the corners come on a lap.
+    pub number: u8,
+    pub direction: Direction,
+    pub start: f32,
+    /// Point of peak lateral acceleration.
+    pub apex: f32,
+    /// Before `start` for a corner through the start and finish line.
+    pub end: f32,
+}
+
+impl Corner {
+    pub fn contains(&self, lap_distance: f32) -> bool {
+        if self.start <= self.end {
+            lap_distance >= self.start && lap_distance < self.end
+        } else {
+            lap_distance >= self.start || lap_distance < self.end
+        }
+    }
+}
+
+/// The corners of a track, as `lap_distance` ranges.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CornerMap {
+    pub track_id: TrackId,
+    pub corners: Vec<Corner>,
+}
+
+impl CornerMap {
+    /// Finds the corners in one lap of samples, taken in lap distance order
+    /// on a track `track_length` metres long.
+    pub fn detect(
+        track_id: TrackId,
+        track_length: f32,
+        samples: &[CornerSample],
+        config: &CornerConfig,
+    ) -> Self {
+        // Turns as (direction, start, apex, end, peak lateral g).
+        let mut turns: Vec<(Direction, f32, f32, f32, f32)> = Vec::new();
+        let mut open = false;
+        for sample in samples {
+            let lateral = sample.lateral_g.abs();
+            if lateral < config.lateral_g && sample.steer.abs() < config.steer {
+                open = false;
+                continue;
+            }
+
+            // Without steering input, the car is pushed the way it turns.
+            let direction = if sample.steer < 0.0 || (sample.steer == 0.0 && sample.lateral_g < 0.0)
+            {
+                Direction::Left
+            } else {
+                Direction::Right
+            };
+            let distance = sample.lap_distance;
+            match turns.last_mut() {
+                Some(turn)
+                    if turn.0 == direction && (open || distance - turn.3 < config.merge_gap) =>
+                {
+                    turn.3 = distance;
+                    if lateral > turn.4 {
+                        turn.2 = distance;
+                        turn.4 = lateral;
+                    }
+                }
+                _ => turns.push((direction, distance, distance, distance, lateral)),
+            }
+            open = true;
+        }
+
+        // A corner through the start and finish line is seen at both ends of
+        // the lap.
+        if turns.len() > 1 {
+            let first = turns[0];
+            let last = turns[turns.len() - 1];
+            if first.0 == last.0 && first.1 + track_length - last.3 < config.merge_gap {
+                turns.remove(0);
+                let turn = turns.last_mut().unwrap();
+                turn.3 = first.3;
+                if first.4 > turn.4 {
+                    turn.2 = first.2;
+                    turn.4 = first.4;
+                }
+            }
+        }
+
+        let length = |start: f32, end: f32| {
+            if start <= end {
+                end - start
+            } else {
+                end + track_length - start
+            }
+        };
+        let corners = turns
+            .into_iter()
+            .filter(|turn| length(turn.1, turn.3) >= config.minimum_length)
+            .enumerate()
+            .map(|(index, (direction, start, apex, end, _))| Corner {
+                number: index as u8 + 1,
+                direction,
+                start,
+                apex,
+                end,
+            })
+            .collect();
+
+        CornerMap { track_id, corners }
+    }
+
+    pub fn corner_at(&self, lap_distance: f32) -> Option<&Corner> {
+        self.corners
+            .iter()
+            .find(|corner| corner.contains(lap_distance))
+    }
+}
+
+/// One car's trip through a corner.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CornerPass {
+    pub vehicle_index: u8,
+    pub lap: u8,
+    pub corner: u8,
+    /// Speeds in km/h.
+    pub entry_speed: u16,
+    pub minimum_speed: u16,
+    pub exit_speed: u16,
+    pub minimum_gear: i8,
+    pub start_time: f32,
+    pub end_time: f32,
+}
+
+impl CornerPass {
+    pub fn duration(&self) -> f32 {
+        self.end_time - self.start_time
+    }
+}
+
+#[derive(Debug, Default)]
+struct CarCorners {
+    lap: u8,
+    lap_distance: f32,
+    lateral_g: Option<f32>,
+    current: Option<CornerPass>,
+}
+
+/// Learns the corners of each track from the player's laps and splits every
+/// car's laps into corner passes.
+#[derive(Debug, Default)]
+pub struct CornerAnalyzer {
+    config: CornerConfig,
+    track_id: Option<TrackId>,
+    track_length: u16,
+    maps: HashMap<TrackId, CornerMap>,
+    trace_lap: Option<u8>,
+    trace: Vec<CornerSample>,
+    cars: Vec<CarCorners>,
+    passes: Vec<CornerPass>,
+}
+
+impl CornerAnalyzer {
+    pub fn new(config: CornerConfig) -> Self {
+        CornerAnalyzer {
+            config,
+            ..CornerAnalyzer::default()
+        }
+    }
+
+    /// Uses a known set of corners for a track instead of detecting them.
+    pub fn insert_map(&mut self, map: CornerMap) -> Option<CornerMap> {
+        self.maps.insert(map.track_id, map)
+    }
+
+    pub fn map(&self, track_id: TrackId) -> Option<&CornerMap> {
+        self.maps.get(&track_id)
+    }
+
+    pub fn passes(&self) -> &[CornerPass] {
+        &self.passes
+    }
+
+    /// Passes through `corner` by one car, in the order they happened.
+    pub fn corner_passes(&self, vehicle_index: u8, corner: u8) -> Vec<&CornerPass> {
+        self.passes
+            .iter()
+            .filter(|pass| pass.vehicle_index == vehicle_index && pass.corner == corner)
+            .collect()
+    }
+
+    /// Returns the corner passes completed by this packet.
+    pub fn update(&mut self, packet: &Telemetry) -> &[CornerPass] {
+        let player = packet.header.player_car_index as usize;
+        let session_time = packet.header.session_time;
+        let completed = self.passes.len();
+
+        match packet.data {
+            TelemetryData::Session(ref data) => {
+                self.track_length = data.track_length;
+                if self.track_id != Some(data.track_id) {
+                    self.track_id = Some(data.track_id);
+                    self.trace_lap = None;
+                    self.trace.clear();
+                }
+            }
+            TelemetryData::Lap(ref data) => {
+                if self.cars.len() < data.lap_data.len() {
+                    self.cars
+                        .resize_with(data.lap_data.len(), CarCorners::default);
+                }
+                for (car, lap) in self.cars.iter_mut().zip(&data.lap_data) {
+                    car.lap = lap.current_lap_num;
+                    car.lap_distance = lap.lap_distance;
+                }
+                if let Some(lap) = data.lap_data.get(player) {
+                    self.update_trace_lap(lap.current_lap_num);
+                }
+            }
+            TelemetryData::Motion(ref data) => {
+                if self.cars.len() < data.car_motion_data.len() {
+                    self.cars
+                        .resize_with(data.car_motion_data.len(), CarCorners::default);
+                }
+                for (car, motion) in self.cars.iter_mut().zip(&data.car_motion_data) {
+                    car.lateral_g = Some(motion.g_force.lateral);
+                }
+            }
+            TelemetryData::CarTelemetry(ref data) => {
+                if let Some(car) = self.cars.get(player) {
+                    if let (Some(lateral_g), Some(telemetry)) =
+                        (car.lateral_g, data.car_telemetry_data.get(player))
+                    {
+                        self.trace.push(CornerSample {
+                            lap_distance: car.lap_distance,
+                            lateral_g,
+                            steer: telemetry.steer,
+                        });
+                    }
+                }
+
+                let maps = &self.maps;
+                let map = match self.track_id.and_then(|track_id| maps.get(&track_id)) {
+                    Some(map) => map,
+                    None => return &[],
+                };
+                for (vehicle, (car, telemetry)) in self
+                    .cars
+                    .iter_mut()
+                    .zip(&data.car_telemetry_data)
+                    .enumerate()
+                {
+                    if car.lateral_g.is_none() {
+                        continue;
+                    }
+
+                    let corner = map.corner_at(car.lap_distance).map(|corner| corner.number);
+                    let in_progress = car.current.as_ref().map(|pass| pass.corner);
+                    if in_progress.is_some() && in_progress != corner {
+                        let mut pass = car.current.take().unwrap();
+                        pass.exit_speed = telemetry.speed;
+                        pass.end_time = session_time;
+                        self.passes.push(pass);
+                    }
+
+                    let corner = match corner {
+                        Some(corner) => corner,
+                        None => continue,
+                    };
+                    let (speed, gear) = (telemetry.speed, telemetry.gear);
+                    let pass = car.current.get_or_insert(CornerPass {
+                        vehicle_index: vehicle as u8,
+                        lap: car.lap,
+                        corner,
+                        entry_speed: speed,
+                        minimum_speed: speed,
+                        exit_speed: speed,
+                        minimum_gear: gear,
+                        start_time: session_time,
+                        end_time: session_time,
+                    });
+                    pass.minimum_speed = pass.minimum_speed.min(speed);
+                    pass.minimum_gear = pass.minimum_gear.min(gear);
+                }
+            }
+            _ => {}
+        }
+
+        &self.passes[completed..]
+    }
+
+    /// Detects the corners from the player's trace once a lap is complete
+    /// at a track without a map. Traces that miss the start or the end of
+    /// the lap are thrown away.
+    fn update_trace_lap(&mut self, lap: u8) {
+        let previous = self.trace_lap.replace(lap);
+        if previous == Some(lap) {
+            return;
+        }
+
+        let track_id = match self.track_id {
+            Some(track_id) if !self.maps.contains_key(&track_id) => track_id,
+            _ => {
+                self.trace.clear();
+                return;
+            }
+        };
+        let track_length = self.track_length as f32;
+        if previous == Some(lap.wrapping_sub(1)) && track_length > 0.0 {
+            self.trace
+                .retain(|sample| (0.0..=track_length).contains(&sample.lap_distance));
+            self.trace
+                .sort_by(|a, b| a.lap_distance.total_cmp(&b.lap_distance));
+            let complete = match (self.trace.first(), self.trace.last()) {
+                (Some(first), Some(last)) => {
+                    first.lap_distance <= LAP_END_TOLERANCE
+                        && track_length - last.lap_distance <= LAP_END_TOLERANCE
+                }
+                _ => false,
+            };
+            if complete {
+                let map = CornerMap::detect(track_id, track_length, &self.trace, &self.config);
+                if !map.corners.is_empty() {
+                    self.maps.insert(track_id, map);
+                }
+            }
+        }
+        self.trace.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{
+        car_motion, car_telemetry, car_telemetry_packet, lap_data, lap_packet, motion_data,
+        motion_packet, session_data, session_packet,
+    };
+    use crate::mappings::SessionType;
+
+    fn sample(lap_distance: f32, lateral_g: f32, steer: f32) -> CornerSample {
+        CornerSample {
+            lap_distance,
+            lateral_g,
+            steer,
+        }
+    }
+
+    #[test]
+    fn test_detect() {
+        let mut samples = Vec::new();
+        for distance in (0..1000).step_by(10) {
+            let distance = distance as f32;
+            let (lateral_g, steer) = match distance as u32 {
+                // A right hander with a short dip in the middle.
+                100..=140 | 170..=200 => (3.0 - (distance - 150.0).abs() / 50.0, 0.3),
+                // A left and right chicane.
+                400..=430 => (2.5, -0.3),
+                440..=470 => (2.5, 0.3),
+                // Too short to count.
+                800 => (2.0, 0.3),
+                _ => (0.1, 0.0),
+            };
+            samples.push(sample(distance, lateral_g, steer));
+        }
+
+        let map = CornerMap::detect(TrackId::Monaco, 1000.0, &samples, &CornerConfig::default());
+        assert_eq!(map.corners.len(), 3);
+        assert_eq!(
+            map.corners[0],
+            Corner {
+                number: 1,
+                direction: Direction::Right,
+                start: 100.0,
+                apex: 140.0,
+                end: 200.0,
+            }
+        );
+        assert_eq!(map.corners[1].direction, Direction::Left);
+        assert_eq!((map.corners[2].start, map.corners[2].end), (440.0, 470.0));
+        assert_eq!(map.corner_at(450.0).map(|corner| corner.number), Some(3));
+        assert!(map.corner_at(300.0).is_none());
+    }
+
+    #[test]
+    fn test_detect_across_the_line() {
+        let samples: Vec<CornerSample> = (0..1000)
+            .step_by(10)
+            .map(|distance| {
+                let distance = distance as f32;
+                match distance as u32 {
+                    // A right hander through the start and finish line.
+                    0..=30 => sample(distance, 2.0, 0.3),
+                    950..=990 => sample(distance, 2.5, 0.3),
+                    // A left hander taken without steering input.
+                    500..=550 => sample(distance, -2.0, 0.0),
+                    _ => sample(distance, 0.1, 0.0),
+                }
+            })
+            .collect();
+
+        let map = CornerMap::detect(TrackId::Monaco, 1000.0, &samples, &CornerConfig::default());
+        assert_eq!(map.corners.len(), 2);
+        assert_eq!(map.corners[0].direction, Direction::Left);
+        assert_eq!(
+            map.corners[1],
+            Corner {
+                number: 2,
+                direction: Direction::Right,
+                start: 950.0,
+                apex: 950.0,
+                end: 30.0,
+            }
+        );
+        assert_eq!(map.corner_at(995.0).map(|corner| corner.number), Some(2));
+        assert_eq!(map.corner_at(10.0).map(|corner| corner.number), Some(2));
+        assert!(map.corner_at(100.0).is_none());
+    }
+
+    #[test]
+    fn test_partial_lap_is_not_learnt() {
+        let mut analyzer = CornerAnalyzer::default();
+        analyzer.update(&session_packet(
+            1,
+            0.0,
+            0,
+            session_data(SessionType::Race, TrackId::Monza),
+        ));
+
+        // Joining halfway round the lap, through a corner at 3000 metres.
+        for step in 0..=50 {
+            let total_distance = 2500.0 + step as f32 * 50.0;
+            let cornering = (3000.0..=3100.0).contains(&total_distance);
+            let mut motion = car_motion(0.0, 0.0);
+            motion.g_force.lateral = if cornering { 3.0 } else { 0.0 };
+            analyzer.update(&motion_packet(1, 0.0, 0, motion_data(vec![motion])));
+            let number = 1 + (total_distance >= 5000.0) as u8;
+            analyzer.update(&lap_packet(
+                1,
+                0.0,
+                0,
+                vec![lap_data(1, number, total_distance)],
+            ));
+            let mut telemetry = car_telemetry(200, 1.0, 0.0);
+            telemetry.steer = if cornering { 0.4 } else { 0.0 };
+            analyzer.update(&car_telemetry_packet(1, 0.0, 0, vec![telemetry]));
+        }
+
+        assert!(analyzer.map(TrackId::Monza).is_none());
+    }
+
+    #[test]
+    fn test_corner_passes() {
+        let mut analyzer = CornerAnalyzer::default();
+        analyzer.update(&session_packet(
+            1,
+            0.0,
+            0,
+            session_data(SessionType::Race, TrackId::Monza),
+        ));
+
+        // Two laps of Monza, with one corner between 1000 and 1100 metres.
+        let mut time = 0.0;
+        for lap in 0..2 {
+            for step in 0..=100 {
+                let total_distance = lap as f32 * 5000.0 + step as f32 * 50.0;
+                let lap_distance = total_distance % 5000.0;
+                let cornering = (1000.0..=1100.0).contains(&lap_distance);
+                let number = lap + 1 + (step == 100) as u8;
+
+                let mut motion = car_motion(0.0, 0.0);
+                motion.g_force.lateral = if cornering { 3.0 } else { 0.0 };
+                analyzer.update(&motion_packet(1, time, 0, motion_data(vec![motion])));
+                analyzer.update(&lap_packet(
+                    1,
+                    time,
+                    0,
+                    vec![lap_data(1, number, total_distance)],
+                ));
+
+                let speed = if cornering {
+                    150 - lap_distance as u16 / 100
+                } else {
+                    300
+                };
+                let mut telemetry = car_telemetry(speed, 1.0, 0.0);
+                telemetry.steer = if cornering { 0.4 } else { 0.0 };
+                telemetry.gear = if cornering { 4 } else { 8 };
+                analyzer.update(&car_telemetry_packet(1, time, 0, vec![telemetry]));
+                time += 1.0;
+            }
+        }
+
+        let map = analyzer.map(TrackId::Monza).unwrap();
+        assert_eq!(map.corners.len(), 1);
+        assert_eq!((map.corners[0].start, map.corners[0].end), (1000.0, 1100.0));
+
+        // The map is learnt at the end of the first lap, so only the second
+        // lap is split up.
+        let passes = analyzer.corner_passes(0, 1);
+        assert_eq!(passes.len(), 1);
+        let pass = passes[0];
+        assert_eq!(pass.lap, 2);
+        assert_eq!((pass.entry_speed, pass.minimum_speed), (140, 140));
+        assert_eq!(pass.exit_speed, 139);
+        assert_eq!(pass.minimum_gear, 4);
+        assert_eq!(pass.duration(), 2.0);
+    }
+}
@@ -0,0 +1,327 @@
+use crate::mappings::ResultStatus;
+use crate::packets::car_status::CarStatusData;
+use crate::packets::motion::GForce;
+use crate::packets::MotionData;
+use crate::{Telemetry, TelemetryData, Wheel};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DamageConfig {
+    /// Change in g-force between two motion packets that counts as an impact.
+    pub impact: f32,
+    /// How long, in seconds, damage reported after an impact is put down to it.
+    pub window: f32,
+    /// Cars closer than this, in metres, at the moment of an impact are
+    /// assumed to have been involved.
+    pub proximity: f32,
+}
+
+impl Default for DamageConfig {
+    fn default() -> Self {
+        DamageConfig {
+            impact: 3.0,
+            window: 1.0,
+            proximity: 5.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DamageComponent {
+    FrontLeftWing,
+    FrontRightWing,
+    RearWing,
+    Engine,
+    Gearbox,
+    Tyre(Wheel),
+}
+
+impl DamageComponent {
+    fn levels(status: &CarStatusData) -> [(DamageComponent, u8); 9] {
+        let tyres = &status.tyres_damage;
+        [
+            (
+                DamageComponent::FrontLeftWing,
+                status.front_left_wing_damage,
+            ),
+            (
+                DamageComponent::FrontRightWing,
+                status.front_right_wing_damage,
+            ),
+            (DamageComponent::RearWing, status.rear_wing_damage),
+            (DamageComponent::Engine, status.engine_damage),
+            (DamageComponent::Gearbox, status.gear_box_damage),
+            (DamageComponent::Tyre(Wheel::RearLeft), tyres.rear_left),
+            (DamageComponent::Tyre(Wheel::RearRight), tyres.rear_right),
+            (DamageComponent::Tyre(Wheel::FrontLeft), tyres.front_left),
+            (DamageComponent::Tyre(Wheel::FrontRight), tyres.front_right),
+        ]
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DamageCause {
+    /// Damage that followed an impact, with the closest car at the time.
+    Contact {
+        impact: f32,
+        other_vehicle: Option<u8>,
+    },
+    /// Damage that built up without any impact.
+    Wear,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DamageIncrease {
+    pub vehicle_index: u8,
+    pub session_time: f32,
+    pub component: DamageComponent,
+    /// Damage percentage before and after.
+    pub from: u8,
+    pub to: u8,
+    pub cause: DamageCause,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Impact {
+    session_time: f32,
+    size: f32,
+    other_vehicle: Option<u8>,
+}
+
+#[derive(Debug, Default)]
+struct CarDamage {
+    /// Set once lap data shows the slot holds no car.
+    inactive: bool,
+    levels: Option<[(DamageComponent, u8); 9]>,
+    g_force: Option<GForce>,
+    impact: Option<Impact>,
+}
+
+/// Reports damage increases of every car and tries to tell contact apart
+/// from wear. Slots that lap data marks as invalid or inactive are left out
+/// of contacts, as their cars sit at the origin.
+#[derive(Debug, Default)]
+pub struct DamageTracker {
+    config: DamageConfig,
+    cars: Vec<CarDamage>,
+    increases: Vec<DamageIncrease>,
+}
+
+impl DamageTracker {
+    pub fn new(config: DamageConfig) -> Self {
+        DamageTracker {
+            config,
+            ..DamageTracker::default()
+        }
+    }
+
+    pub fn increases(&self) -> &[DamageIncrease] {
+        &self.increases
+    }
+
+    fn car(&mut self, vehicle_index: usize) -> &mut CarDamage {
+        if self.cars.len() <= vehicle_index {
+            self.cars.resize_with(vehicle_index + 1, CarDamage::default);
+        }
+        &mut self.cars[vehicle_index]
+    }
+
+    /// Returns the damage increases reported by this packet.
+    pub fn update(&mut self, packet: &Telemetry) -> Vec<DamageIncrease> {
+        let session_time = packet.header.session_time;
+        match packet.data {
+            TelemetryData::Lap(ref data) => {
+                for (index, lap) in data.lap_data.iter().enumerate() {
+                    self.car(index).inactive = matches!(
+                        lap.result_status,
+                        ResultStatus::Invalid | ResultStatus::Inactive
+                    );
+                }
+                Vec::new()
+            }
+            TelemetryData::Motion(ref data) => {
+                self.update_motion(session_time, data);
+                Vec::new()
+            }
+            TelemetryData::CarStatus(ref data) => {
+                let mut increases = Vec::new();
+                for (index, status) in data.car_status_data.iter().enumerate() {
+                    self.update_status(session_time, index, status, &mut increases);
+                }
+                self.increases.extend_from_slice(&increases);
+                increases
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn update_motion(&mut self, session_time: f32, data: &MotionData) {
+        let positions: Vec<_> = data
+            .car_motion_data
+            .iter()
+            .enumerate()
+            .map(|(index, car)| (index, car.world_position))
+            .filter(|(index, _)| !matches!(self.cars.get(*index), Some(car) if car.inactive))
+            .collect();
+
+        for (index, car) in data.car_motion_data.iter().enumerate() {
+            let config = self.config;
+            let state = self.car(index);
+            if state.inactive {
+                continue;
+            }
+            let previous = match state.g_force.replace(car.g_force) {
+                Some(previous) => previous,
+                None => continue,
+            };
+
+            let size = ((car.g_force.lateral - previous.lateral).powi(2)
+                + (car.g_force.longitudinal - previous.longitudinal).powi(2)
+                + (car.g_force.vertical - previous.vertical).powi(2))
+            .sqrt();
+            if size < config.impact {
+                continue;
+            }
+
+            let position = &car.world_position;
+            let other_vehicle = positions
+                .iter()
+                .filter(|(other, _)| *other != index)
+                .map(|(other, other_position)| (*other, position.distance(other_position)))
+                .filter(|(_, distance)| *distance <= config.proximity)
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(other, _)| other as u8);
+
+            state.impact = Some(Impact {
+                session_time,
+                size,
+                other_vehicle,
+            });
+        }
+    }
+
+    fn update_status(
+        &mut self,
+        session_time: f32,
+        index: usize,
+        status: &CarStatusData,
+        increases: &mut Vec<DamageIncrease>,
+    ) {
+        let window = self.config.window;
+        let car = self.car(index);
+        let levels = DamageComponent::levels(status);
+        let previous = match car.levels.replace(levels) {
+            Some(previous) => previous,
+            None => return,
+        };
+
+        let cause = match car.impact {
+            Some(impact) if session_time - impact.session_time <= window => DamageCause::Contact {
+                impact: impact.size,
+                other_vehicle: impact.other_vehicle,
+            },
+            _ => DamageCause::Wear,
+        };
+
+        for ((component, from), (_, to)) in previous.iter().zip(levels.iter()) {
+            if to > from {
+                increases.push(DamageIncrease {
+                    vehicle_index: index as u8,
+                    session_time,
+                    component: *component,
+                    from: *from,
+                    to: *to,
+                    cause,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{
+        car_motion, car_status, car_status_packet, compound, lap_data, lap_packet, motion_data,
+        motion_packet,
+    };
+
+    fn statuses(front_left_wing: u8, gearbox: u8) -> Vec<CarStatusData> {
+        let mut damaged = car_status(compound(18));
+        damaged.front_left_wing_damage = front_left_wing;
+        damaged.gear_box_damage = gearbox;
+        vec![damaged, car_status(compound(18)), car_status(compound(18))]
+    }
+
+    #[test]
+    fn test_contact_and_wear() {
+        let mut tracker = DamageTracker::default();
+        let cars = vec![
+            car_motion(0.0, 0.0),
+            car_motion(1.5, 2.0),
+            car_motion(100.0, 0.0),
+        ];
+        tracker.update(&motion_packet(1, 0.0, 0, motion_data(cars.clone())));
+        assert!(tracker
+            .update(&car_status_packet(1, 0.0, 0, statuses(0, 10)))
+            .is_empty());
+
+        let mut hit = cars;
+        hit[0].g_force.lateral = 4.5;
+        tracker.update(&motion_packet(1, 1.0, 1, motion_data(hit)));
+        let increases = tracker.update(&car_status_packet(1, 1.2, 1, statuses(25, 10)));
+        assert_eq!(
+            increases,
+            vec![DamageIncrease {
+                vehicle_index: 0,
+                session_time: 1.2,
+                component: DamageComponent::FrontLeftWing,
+                from: 0,
+                to: 25,
+                cause: DamageCause::Contact {
+                    impact: 4.5,
+                    other_vehicle: Some(1),
+                },
+            }]
+        );
+
+        let increases = tracker.update(&car_status_packet(1, 10.0, 2, statuses(25, 12)));
+        assert_eq!(increases.len(), 1);
+        assert_eq!(increases[0].component, DamageComponent::Gearbox);
+        assert_eq!(increases[0].cause, DamageCause::Wear);
+        assert_eq!(tracker.increases().len(), 2);
+    }
+
+    #[test]
+    fn test_inactive_slots_are_not_blamed() {
+        let mut tracker = DamageTracker::default();
+        let mut empty = lap_data(0, 0, 0.0);
+        empty.result_status = ResultStatus::Inactive;
+        tracker.update(&lap_packet(
+            1,
+            0.0,
+            0,
+            vec![lap_data(1, 1, 0.0), lap_data(2, 1, 0.0), empty],
+        ));
+
+        // The empty slot sits at the origin, right on top of car 0.
+        let cars = vec![
+            car_motion(0.0, 0.0),
+            car_motion(3.0, 0.0),
+            car_motion(0.0, 0.0),
+        ];
+        tracker.update(&motion_packet(1, 0.0, 0, motion_data(cars.clone())));
+        tracker.update(&car_status_packet(1, 0.0, 0, statuses(0, 0)));
+
+        let mut hit = cars;
+        hit[0].g_force.lateral = 4.0;
+        tracker.update(&motion_packet(1, 1.0, 1, motion_data(hit)));
+        let increases = tracker.update(&car_status_packet(1, 1.1, 1, statuses(10, 0)));
+        assert_eq!(
+            increases[0].cause,
+            DamageCause::Contact {
+                impact: 4.0,
+                other_vehicle: Some(1),
+            }
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+
+use crate::analysis::stats::linear_fit;
+use crate::analysis::strategy::{CompoundModel, StintLap, DEFAULT_FUEL_EFFECT};
+use crate::mappings::{TrackId, TyreCompound};
+
+/// Fitted degradation of one compound.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DegradationCurve {
+    pub compound: TyreCompound,
+    /// Fuel corrected lap time on new tyres.
+    pub pace: f32,
+    /// Time lost per lap of tyre age.
+    pub per_lap: f32,
+    /// Time lost per percent of tyre wear.
+    pub per_wear: Option<f32>,
+    /// Tyre wear added each lap, in percent.
+    pub wear_per_lap: Option<f32>,
+    /// Number of laps the curve was fitted from.
+    pub samples: usize,
+}
+
+impl DegradationCurve {
+    /// Lap time lost to a new tyre after `tyre_age` laps.
+    pub fn delta(&self, tyre_age: u8) -> f32 {
+        self.per_lap * tyre_age as f32
+    }
+
+    /// Laps before the tyres reach `wear_limit` percent wear.
+    pub fn life(&self, wear_limit: f32) -> Option<u8> {
+        match self.wear_per_lap {
+            Some(rate) if rate > 0.0 => Some((wear_limit / rate).min(255.0) as u8),
+            _ => None,
+        }
+    }
+}
+
+/// Degradation curves of every compound run at one track.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DegradationModel {
+    pub track_id: TrackId,
+    /// Lap time added by each kilogram of fuel.
+    pub fuel_effect: f32,
+    pub curves: Vec<DegradationCurve>,
+}
+
+impl DegradationModel {
+    /// Fits a curve for each compound with clean laps at two or more tyre
+    /// ages. In and out laps, the opening lap and laps without a time are
+    /// left out.
+    pub fn fit(track_id: TrackId, laps: &[StintLap], fuel_effect: f32) -> Self {
+        let mut compounds: Vec<TyreCompound> = Vec::new();
+        for lap in laps {
+            if !compounds.contains(&lap.compound) {
+                compounds.push(lap.compound);
+            }
+        }
+
+        let curves = compounds
+            .into_iter()
+            .filter_map(|compound| {
+                let clean: Vec<&StintLap> = laps
+                    .iter()
+                    .filter(|lap| {
+                        lap.compound == compound && !lap.pit && lap.lap > 1 && lap.lap_time > 0.0
+                    })
+                    .collect();
+                let corrected = |lap: &StintLap| lap.lap_time - fuel_effect * lap.fuel_in_tank;
+
+                let by_age: Vec<(f32, f32)> = clean
+                    .iter()
+                    .map(|lap| (lap.tyre_age as f32, corrected(lap)))
+                    .collect();
+                let (pace, per_lap) = linear_fit(&by_age)?;
+                let by_wear: Vec<(f32, f32)> = clean
+                    .iter()
+                    .map(|lap| (lap.tyre_wear, corrected(lap)))
+                    .collect();
+                // Wear is read at the end of the lap, after `tyre_age + 1` laps.
+                let wear: Vec<(f32, f32)> = clean
+                    .iter()
+                    .map(|lap| (lap.tyre_age as f32 + 1.0, lap.tyre_wear))
+                    .collect();
+
+                Some(DegradationCurve {
+                    compound,
+                    pace,
+                    per_lap,
+                    per_wear: linear_fit(&by_wear).map(|(_, slope)| slope),
+                    wear_per_lap: linear_fit(&wear).map(|(_, slope)| slope),
+                    samples: clean.len(),
+                })
+            })
+            .collect();
+
+        DegradationModel {
+            track_id,
+            fuel_effect,
+            curves,
+        }
+    }
+
+    pub fn curve(&self, compound: TyreCompound) -> Option<&DegradationCurve> {
+        self.curves.iter().find(|curve| curve.compound == compound)
+    }
+
+    /// Predicted time of a lap started on tyres `tyre_age` laps old with
+    /// `fuel_in_tank` kilograms of fuel.
+    pub fn lap_time(&self, compound: TyreCompound, tyre_age: u8, fuel_in_tank: f32) -> Option<f32> {
+        let curve = self.curve(compound)?;
+        Some(curve.pace + curve.delta(tyre_age) + self.fuel_effect * fuel_in_tank.max(0.0))
+    }
+
+    /// Predicted lap times of a stint of `laps` laps on new tyres, starting
+    /// with `fuel_in_tank` kilograms and burning `fuel_per_lap` each lap.
+    pub fn stint(
+        &self,
+        compound: TyreCompound,
+        laps: u8,
+        fuel_in_tank: f32,
+        fuel_per_lap: f32,
+    ) -> Option<Vec<f32>> {
+        (0..laps)
+            .map(|lap| self.lap_time(compound, lap, fuel_in_tank - fuel_per_lap * lap as f32))
+            .collect()
+    }
+
+    /// The curves as inputs to the strategy simulator. Tyres last until they
+    /// reach `wear_limit` percent wear, or indefinitely when the wear rate is
+    /// unknown.
+    pub fn compound_models(&self, wear_limit: f32) -> Vec<CompoundModel> {
+        self.curves
+            .iter()
+            .map(|curve| CompoundModel {
+                compound: curve.compound,
+                pace: curve.pace,
+                degradation: curve.per_lap,
+                max_laps: curve.life(wear_limit).unwrap_or(u8::MAX),
+            })
+            .collect()
+    }
+}
+
+/// Laps recorded across sessions, grouped by track.
+#[derive(Debug)]
+pub struct DegradationLibrary {
+    fuel_effect: f32,
+    laps: HashMap<TrackId, Vec<StintLap>>,
+}
+
+impl Default for DegradationLibrary {
+    fn default() -> Self {
+        DegradationLibrary::new(DEFAULT_FUEL_EFFECT)
+    }
+}
+
+impl DegradationLibrary {
+    pub fn new(fuel_effect: f32) -> Self {
+        DegradationLibrary {
+            fuel_effect,
+            laps: HashMap::new(),
+        }
+    }
+
+    /// Adds the laps of one car from a session at `track_id`.
+    pub fn record(&mut self, track_id: TrackId, laps: &[StintLap]) {
+        self.laps
+            .entry(track_id)
+            .or_default()
+            .extend_from_slice(laps);
+    }
+
+    pub fn laps(&self, track_id: TrackId) -> &[StintLap] {
+        self.laps.get(&track_id).map_or(&[], |laps| &laps[..])
+    }
+
+    pub fn model(&self, track_id: TrackId) -> Option<DegradationModel> {
+        let laps = self.laps.get(&track_id)?;
+        Some(DegradationModel::fit(track_id, laps, self.fuel_effect))
+    }
+
+    /// Predicted lap times of a stint at `track_id`.
+    pub fn predict_stint(
+        &self,
+        track_id: TrackId,
+        compound: TyreCompound,
+        laps: u8,
+        fuel_in_tank: f32,
+        fuel_per_lap: f32,
+    ) -> Option<Vec<f32>> {
+        self.model(track_id)?
+            .stint(compound, laps, fuel_in_tank, fuel_per_lap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::compound;
+
+    /// A stint losing `per_lap` seconds and 3% wear a lap, burning 1.5 kg
+    /// of fuel a lap from 100 kg.
+    fn stint(
+        compound: TyreCompound,
+        first_lap: u8,
+        laps: u8,
+        pace: f32,
+        per_lap: f32,
+    ) -> Vec<StintLap> {
+        (0..laps)
+            .map(|age| {
+                let lap = first_lap + age;
+                let fuel_in_tank = 100.0 - 1.5 * lap as f32;
+                StintLap {
+                    lap,
+                    lap_time: pace + per_lap * age as f32 + DEFAULT_FUEL_EFFECT * fuel_in_tank,
+                    compound,
+                    tyre_age: age,
+                    tyre_wear: 3.0 * (age as f32 + 1.0),
+                    fuel_in_tank,
+                    pit: false,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fit() {
+        let mut laps = stint(compound(16), 1, 12, 80.0, 0.2);
+        laps.extend(stint(compound(18), 13, 20, 80.6, 0.05));
+        // A slow in lap that should be left out.
+        laps[11].pit = true;
+        laps[11].lap_time += 20.0;
+
+        let model = DegradationModel::fit(TrackId::Spa, &laps, DEFAULT_FUEL_EFFECT);
+        assert_eq!(model.curves.len(), 2);
+
+        let soft = model.curve(compound(16)).unwrap();
+        assert_eq!(soft.samples, 10);
+        assert!((soft.pace - 80.0).abs() < 1e-2);
+        assert!((soft.per_lap - 0.2).abs() < 1e-3);
+        assert!((soft.wear_per_lap.unwrap() - 3.0).abs() < 1e-3);
+        assert!((soft.per_wear.unwrap() - 0.2 / 3.0).abs() < 1e-3);
+        assert_eq!(soft.life(60.0), Some(20));
+
+        let times = model.stint(compound(18), 3, 50.0, 1.5).unwrap();
+        assert!((times[0] - (80.6 + 1.5)).abs() < 1e-2);
+        assert!((times[2] - (80.6 + 0.1 + 0.03 * 47.0)).abs() < 1e-2);
+        assert_eq!(model.stint(compound(17), 3, 50.0, 1.5), None);
+
+        let models = model.compound_models(60.0);
+        assert_eq!(models[1].max_laps, 20);
+        assert!((models[1].degradation - 0.05).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_library() {
+        let mut library = DegradationLibrary::default();
+        library.record(TrackId::Monza, &stint(compound(17), 2, 5, 79.0, 0.1));
+        library.record(TrackId::Monza, &stint(compound(17), 2, 5, 79.0, 0.1));
+
+        assert_eq!(library.laps(TrackId::Monza).len(), 10);
+        assert!(library.model(TrackId::Spa).is_none());
+        let times = library
+            .predict_stint(TrackId::Monza, compound(17), 2, 0.0, 0.0)
+            .unwrap();
+        assert!((times[1] - 79.1).abs() < 1e-2);
+    }
+}
@@ -0,0 +1,318 @@
+use crate::analysis::stats::Summary;
+use crate::packets::CarTelemetryData;
+use crate::{Telemetry, TelemetryData};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DrivingThresholds {
+    /// Pedal travel, between 0 and 1, below which a pedal counts as released.
+    pub pedal_off: f32,
+    /// Pedal travel from which a pedal counts as pressed.
+    pub pedal_on: f32,
+    /// Cars slower than this, in km/h, are not counted as coasting.
+    pub coasting_speed: u16,
+    /// Upshifts before the rev lights reach this percentage are early.
+    pub early_shift_lights: u8,
+    /// Upshifts this long, in seconds, after the rev lights are full are late.
+    pub late_shift_after: f32,
+}
+
+impl Default for DrivingThresholds {
+    fn default() -> Self {
+        DrivingThresholds {
+            pedal_off: 0.05,
+            pedal_on: 0.1,
+            coasting_speed: 50,
+            early_shift_lights: 80,
+            late_shift_after: 0.2,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct ShiftCounts {
+    pub early: u32,
+    pub on_time: u32,
+    pub late: u32,
+}
+
+/// How the player used the controls over one lap.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct DrivingLap {
+    pub lap: u8,
+    pub lap_time: f32,
+    /// Time covered by telemetry samples.
+    pub duration: f32,
+    /// Time spent off both pedals.
+    pub coasting_time: f32,
+    /// Time spent on the throttle and the brake together.
+    pub overlap_time: f32,
+    /// Change of each input per second. Lower is smoother.
+    pub throttle_rate: Summary,
+    pub brake_rate: Summary,
+    pub steer_rate: Summary,
+    pub upshifts: ShiftCounts,
+    pub downshifts: u32,
+    /// Rev lights percentage at each upshift.
+    pub upshift_lights: Summary,
+    /// Engine speed at each upshift.
+    pub upshift_rpm: Summary,
+}
+
+impl DrivingLap {
+    pub fn coasting_ratio(&self) -> f32 {
+        if self.duration > 0.0 {
+            self.coasting_time / self.duration
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Spread of the player's laps.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Consistency {
+    pub laps: usize,
+    pub mean_lap_time: f32,
+    /// Standard deviations across laps.
+    pub lap_time_deviation: f32,
+    pub coasting_deviation: f32,
+    pub overlap_deviation: f32,
+}
+
+impl Consistency {
+    /// 100 for identical lap times, losing 10 points for each percent the
+    /// lap times vary by.
+    pub fn score(&self) -> f32 {
+        let variation = self.lap_time_deviation / self.mean_lap_time;
+        (100.0 - 1000.0 * variation).clamp(0.0, 100.0)
+    }
+}
+
+fn mean_and_deviation(values: &[f32]) -> (f32, f32) {
+    let mean = values.iter().sum::<f32>() / values.len() as f32;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / values.len() as f32;
+    (mean, variance.sqrt())
+}
+
+/// Scores the player's throttle, brake, steering and gear changes lap by lap.
+#[derive(Debug, Default)]
+pub struct DrivingAnalyzer {
+    thresholds: DrivingThresholds,
+    lap: Option<u8>,
+    last: Option<(f32, CarTelemetryData)>,
+    /// When the rev lights last became full.
+    full_lights: Option<f32>,
+    current: Option<DrivingLap>,
+    laps: Vec<DrivingLap>,
+}
+
+impl DrivingAnalyzer {
+    pub fn new(thresholds: DrivingThresholds) -> Self {
+        DrivingAnalyzer {
+            thresholds,
+            ..DrivingAnalyzer::default()
+        }
+    }
+
+    pub fn laps(&self) -> &[DrivingLap] {
+        &self.laps
+    }
+
+    /// Returns the lap completed by this packet, if any.
+    pub fn update(&mut self, packet: &Telemetry) -> Option<&DrivingLap> {
+        let player = packet.header.player_car_index as usize;
+        let session_time = packet.header.session_time;
+
+        match packet.data {
+            TelemetryData::Lap(ref data) => {
+                if let Some(lap) = data.lap_data.get(player) {
+                    if self.update_lap(lap.current_lap_num, lap.last_lap_time) {
+                        return self.laps.last();
+                    }
+                }
+            }
+            TelemetryData::CarTelemetry(ref data) => {
+                if let Some(telemetry) = data.car_telemetry_data.get(player) {
+                    self.update_telemetry(session_time, telemetry);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Returns true when a lap has just been completed. Nothing is recorded
+    /// until the first lap change, so a lap joined part way is left out.
+    fn update_lap(&mut self, lap: u8, last_lap_time: f32) -> bool {
+        let previous = self.lap.replace(lap);
+        if previous.is_none() || previous == Some(lap) {
+            return false;
+        }
+
+        let completed = self.current.replace(DrivingLap {
+            lap,
+            ..DrivingLap::default()
+        });
+        match completed {
+            Some(mut completed) if previous == Some(lap.wrapping_sub(1)) => {
+                completed.lap_time = last_lap_time;
+                self.laps.push(completed);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn update_telemetry(&mut self, session_time: f32, telemetry: &CarTelemetryData) {
+        let thresholds = self.thresholds;
+        let last = self.last.replace((session_time, *telemetry));
+        let full_lights = self.full_lights;
+        self.full_lights = if telemetry.rev_lights_percentage >= 100 {
+            Some(full_lights.unwrap_or(session_time))
+        } else {
+            None
+        };
+
+        let (lap, (last_time, previous)) = match (self.current.as_mut(), last) {
+            (Some(lap), Some(last)) => (lap, last),
+            _ => return,
+        };
+
+        let dt = session_time - last_time;
+        if dt > 0.0 {
+            lap.duration += dt;
+            lap.throttle_rate
+                .add((telemetry.throttle - previous.throttle).abs() / dt);
+            lap.brake_rate
+                .add((telemetry.brake - previous.brake).abs() / dt);
+            lap.steer_rate
+                .add((telemetry.steer - previous.steer).abs() / dt);
+
+            if telemetry.throttle < thresholds.pedal_off
+                && telemetry.brake < thresholds.pedal_off
+                && telemetry.speed >= thresholds.coasting_speed
+            {
+                lap.coasting_time += dt;
+            }
+            if telemetry.throttle >= thresholds.pedal_on && telemetry.brake >= thresholds.pedal_on {
+                lap.overlap_time += dt;
+            }
+        }
+
+        if previous.gear > 0 && telemetry.gear > previous.gear {
+            lap.upshift_lights
+                .add(previous.rev_lights_percentage as f32);
+            lap.upshift_rpm.add(previous.engine_rpm as f32);
+            if previous.rev_lights_percentage < thresholds.early_shift_lights {
+                lap.upshifts.early += 1;
+            } else if matches!(full_lights, Some(since) if last_time - since > thresholds.late_shift_after)
+            {
+                lap.upshifts.late += 1;
+            } else {
+                lap.upshifts.on_time += 1;
+            }
+        } else if telemetry.gear > 0 && telemetry.gear < previous.gear {
+            lap.downshifts += 1;
+        }
+    }
+
+    /// Lap to lap spread of the completed laps, needing at least two laps.
+    pub fn consistency(&self) -> Option<Consistency> {
+        let laps: Vec<&DrivingLap> = self.laps.iter().filter(|lap| lap.lap_time > 0.0).collect();
+        if laps.len() < 2 {
+            return None;
+        }
+
+        let values = |f: fn(&DrivingLap) -> f32| laps.iter().map(|lap| f(lap)).collect::<Vec<_>>();
+        let (mean_lap_time, lap_time_deviation) = mean_and_deviation(&values(|lap| lap.lap_time));
+        Some(Consistency {
+            laps: laps.len(),
+            mean_lap_time,
+            lap_time_deviation,
+            coasting_deviation: mean_and_deviation(&values(|lap| lap.coasting_time)).1,
+            overlap_deviation: mean_and_deviation(&values(|lap| lap.overlap_time)).1,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{
+        car_telemetry, car_telemetry_packet, lap_data, lap_packet,
+    };
+
+    fn lap(
+        analyzer: &mut DrivingAnalyzer,
+        number: u8,
+        time: f32,
+        last_lap_time: f32,
+    ) -> Option<DrivingLap> {
+        let mut data = lap_data(1, number, 0.0);
+        data.last_lap_time = last_lap_time;
+        analyzer
+            .update(&lap_packet(1, time, 0, vec![data]))
+            .copied()
+    }
+
+    #[test]
+    fn test_inputs_and_shifts() {
+        let mut analyzer = DrivingAnalyzer::new(DrivingThresholds::default());
+        assert!(lap(&mut analyzer, 1, 0.0, 0.0).is_none());
+        assert!(lap(&mut analyzer, 2, 0.0, 0.0).is_none());
+
+        // Ten samples a tenth of a second apart.
+        let mut samples = Vec::new();
+        for tick in 0..10 {
+            let (throttle, brake) = match tick {
+                0..=2 => (1.0, 0.0),
+                3..=4 => (0.0, 0.0),
+                5 => (0.5, 0.5),
+                _ => (0.0, 1.0),
+            };
+            let mut telemetry = car_telemetry(200, throttle, brake);
+            telemetry.gear = 4 + (tick >= 1) as i8 + (tick >= 2) as i8 - (tick >= 8) as i8;
+            telemetry.rev_lights_percentage = if tick == 0 { 60 } else { 100 };
+            samples.push(telemetry);
+        }
+        for (tick, telemetry) in samples.into_iter().enumerate() {
+            let time = tick as f32 * 0.1;
+            analyzer.update(&car_telemetry_packet(1, time, 0, vec![telemetry]));
+        }
+
+        let completed = lap(&mut analyzer, 3, 1.0, 80.0).unwrap();
+        assert_eq!(completed.lap, 2);
+        assert_eq!(completed.lap_time, 80.0);
+        assert!((completed.duration - 0.9).abs() < 1e-4);
+        assert!((completed.coasting_time - 0.2).abs() < 1e-4);
+        assert!((completed.overlap_time - 0.1).abs() < 1e-4);
+        assert!((completed.throttle_rate.max - 10.0).abs() < 1e-3);
+        assert_eq!(
+            completed.upshifts,
+            ShiftCounts {
+                early: 1,
+                on_time: 1,
+                late: 0,
+            }
+        );
+        assert_eq!(completed.downshifts, 1);
+        assert_eq!(completed.upshift_lights.mean(), Some(80.0));
+    }
+
+    #[test]
+    fn test_consistency() {
+        let mut analyzer = DrivingAnalyzer::default();
+        // Joined part way through the first lap, which is left out.
+        lap(&mut analyzer, 1, 0.0, 0.0);
+        assert!(lap(&mut analyzer, 2, 40.0, 79.5).is_none());
+        lap(&mut analyzer, 3, 119.0, 79.0);
+        assert!(analyzer.consistency().is_none());
+        lap(&mut analyzer, 4, 200.0, 81.0);
+
+        let consistency = analyzer.consistency().unwrap();
+        assert_eq!(consistency.laps, 2);
+        assert_eq!(consistency.mean_lap_time, 80.0);
+        assert_eq!(consistency.lap_time_deviation, 1.0);
+        assert!((consistency.score() - 87.5).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,387 @@
+use std::fmt::Write;
+
+use crate::analysis::overtakes::{OvertakeDetector, PositionChange};
+use crate::mappings::{Flag, PitStatus, ResultStatus, TeamId};
+use crate::packets::{EventDataDetails, LapData, MarshalZone, ParticipantData};
+use crate::{Telemetry, TelemetryData};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Driver {
+    pub name: String,
+    pub team_id: TeamId,
+    pub race_number: u8,
+}
+
+impl<'a> From<&ParticipantData<'a>> for Driver {
+    fn from(participant: &ParticipantData<'a>) -> Self {
+        Driver {
+            name: participant.name.to_owned(),
+            team_id: participant.team_id,
+            race_number: participant.race_number,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RaceEventKind {
+    SessionStarted,
+    SessionEnded,
+    FastestLap {
+        vehicle_index: u8,
+        lap_time: f32,
+    },
+    Retirement {
+        vehicle_index: u8,
+    },
+    DRSEnabled,
+    DRSDisabled,
+    TeamMateInPits {
+        vehicle_index: u8,
+    },
+    ChequeredFlag,
+    RaceWinner {
+        vehicle_index: u8,
+    },
+    Overtake {
+        by: u8,
+        on: u8,
+        lap: u8,
+    },
+    PitEntry {
+        vehicle_index: u8,
+        lap: u8,
+    },
+    PitExit {
+        vehicle_index: u8,
+        lap: u8,
+    },
+    Penalty {
+        vehicle_index: u8,
+        added: u8,
+        total: u8,
+    },
+    MarshalZoneFlag {
+        zone: usize,
+        flag: Flag,
+    },
+}
+
+impl From<EventDataDetails> for RaceEventKind {
+    fn from(details: EventDataDetails) -> Self {
+        match details {
+            EventDataDetails::SessionStarted => RaceEventKind::SessionStarted,
+            EventDataDetails::SessionEnded => RaceEventKind::SessionEnded,
+            EventDataDetails::FastestLap {
+                vehicle_index,
+                lap_time,
+            } => RaceEventKind::FastestLap {
+                vehicle_index,
+                lap_time,
+            },
+            EventDataDetails::Retirement { vehicle_index } => {
+                RaceEventKind::Retirement { vehicle_index }
+            }
+            EventDataDetails::DRSEnabled => RaceEventKind::DRSEnabled,
+            EventDataDetails::DRSDisabled => RaceEventKind::DRSDisabled,
+            EventDataDetails::TeamMateInPits { vehicle_index } => {
+                RaceEventKind::TeamMateInPits { vehicle_index }
+            }
+            EventDataDetails::ChequeredFlag => RaceEventKind::ChequeredFlag,
+            EventDataDetails::RaceWinner { vehicle_index } => {
+                RaceEventKind::RaceWinner { vehicle_index }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RaceEvent {
+    pub session_time: f32,
+    pub kind: RaceEventKind,
+}
+
+/// Collects game events and events derived from lap and session data into a
+/// single race timeline.
+#[derive(Debug, Default)]
+pub struct EventLog {
+    drivers: Vec<Driver>,
+    previous_laps: Vec<LapData>,
+    overtakes: OvertakeDetector,
+    zone_flags: Vec<Flag>,
+    events: Vec<RaceEvent>,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        EventLog::default()
+    }
+
+    pub fn events(&self) -> &[RaceEvent] {
+        &self.events
+    }
+
+    pub fn driver(&self, vehicle_index: u8) -> Option<&Driver> {
+        self.drivers.get(vehicle_index as usize)
+    }
+
+    pub fn update(&mut self, packet: &Telemetry) {
+        let time = packet.header.session_time;
+
+        match packet.data {
+            TelemetryData::Participants(ref data) => {
+                self.drivers = data.participants.iter().map(Driver::from).collect();
+            }
+            TelemetryData::Event(ref event) => self.push(time, event.event_details.into()),
+            TelemetryData::Lap(ref data) => self.update_laps(time, &data.lap_data),
+            TelemetryData::Session(ref data) => self.update_zones(time, &data.marshal_zones),
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, session_time: f32, kind: RaceEventKind) {
+        self.events.push(RaceEvent { session_time, kind });
+    }
+
+    fn update_laps(&mut self, time: f32, laps: &[LapData]) {
+        for change in self.overtakes.update(time, laps) {
+            if let PositionChange::Overtake(overtake) = change {
+                self.push(
+                    time,
+                    RaceEventKind::Overtake {
+                        by: overtake.by,
+                        on: overtake.on,
+                        lap: overtake.lap,
+                    },
+                );
+            }
+        }
+
+        let previous_laps = std::mem::replace(&mut self.previous_laps, laps.to_vec());
+
+        for (index, (previous, current)) in previous_laps.iter().zip(laps).enumerate() {
+            let vehicle_index = index as u8;
+            if !is_running(previous) || !is_running(current) {
+                continue;
+            }
+
+            match (previous.pit_status, current.pit_status) {
+                (PitStatus::None, PitStatus::Pitting) | (PitStatus::None, PitStatus::InPitArea) => {
+                    self.push(
+                        time,
+                        RaceEventKind::PitEntry {
+                            vehicle_index,
+                            lap: current.current_lap_num,
+                        },
+                    )
+                }
+                (PitStatus::Pitting, PitStatus::None) | (PitStatus::InPitArea, PitStatus::None) => {
+                    self.push(
+                        time,
+                        RaceEventKind::PitExit {
+                            vehicle_index,
+                            lap: current.current_lap_num,
+                        },
+                    )
+                }
+                _ => {}
+            }
+
+            if current.penalties > previous.penalties {
+                self.push(
+                    time,
+                    RaceEventKind::Penalty {
+                        vehicle_index,
+                        added: current.penalties - previous.penalties,
+                        total: current.penalties,
+                    },
+                );
+            }
+        }
+    }
+
+    fn update_zones(&mut self, time: f32, zones: &[MarshalZone]) {
+        if self.zone_flags.len() != zones.len() {
+            self.zone_flags = zones.iter().map(|zone| zone.zone_flag).collect();
+            return;
+        }
+
+        for (zone, marshal_zone) in zones.iter().enumerate() {
+            if self.zone_flags[zone] != marshal_zone.zone_flag {
+                self.zone_flags[zone] = marshal_zone.zone_flag;
+                self.push(
+                    time,
+                    RaceEventKind::MarshalZoneFlag {
+                        zone,
+                        flag: marshal_zone.zone_flag,
+                    },
+                );
+            }
+        }
+    }
+
+    fn describe_driver(&self, vehicle_index: u8) -> String {
+        match self.driver(vehicle_index) {
+            Some(driver) => format!(
+                "#{} {} ({:?})",
+                driver.race_number, driver.name, driver.team_id
+            ),
+            None => format!("Car {}", vehicle_index),
+        }
+    }
+
+    pub fn describe(&self, event: &RaceEvent) -> String {
+        match event.kind {
+            RaceEventKind::SessionStarted => "Session started".to_owned(),
+            RaceEventKind::SessionEnded => "Session ended".to_owned(),
+            RaceEventKind::FastestLap {
+                vehicle_index,
+                lap_time,
+            } => format!(
+                "Fastest lap: {} {}",
+                self.describe_driver(vehicle_index),
+                format_time(lap_time)
+            ),
+            RaceEventKind::Retirement { vehicle_index } => {
+                format!("Retired: {}", self.describe_driver(vehicle_index))
+            }
+            RaceEventKind::DRSEnabled => "DRS enabled".to_owned(),
+            RaceEventKind::DRSDisabled => "DRS disabled".to_owned(),
+            RaceEventKind::TeamMateInPits { vehicle_index } => {
+                format!("Team mate in pits: {}", self.describe_driver(vehicle_index))
+            }
+            RaceEventKind::ChequeredFlag => "Chequered flag".to_owned(),
+            RaceEventKind::RaceWinner { vehicle_index } => {
+                format!("Race winner: {}", self.describe_driver(vehicle_index))
+            }
+            RaceEventKind::Overtake { by, on, lap } => format!(
+                "{} overtook {} on lap {}",
+                self.describe_driver(by),
+                self.describe_driver(on),
+                lap
+            ),
+            RaceEventKind::PitEntry { vehicle_index, lap } => format!(
+                "{} entered the pits on lap {}",
+                self.describe_driver(vehicle_index),
+                lap
+            ),
+            RaceEventKind::PitExit { vehicle_index, lap } => format!(
+                "{} left the pits on lap {}",
+                self.describe_driver(vehicle_index),
+                lap
+            ),
+            RaceEventKind::Penalty {
+                vehicle_index,
+                added,
+                total,
+            } => format!(
+                "{} received a {}s penalty ({}s total)",
+                self.describe_driver(vehicle_index),
+                added,
+                total
+            ),
+            RaceEventKind::MarshalZoneFlag { zone, flag } => {
+                format!("Marshal zone {}: {:?} flag", zone + 1, flag)
+            }
+        }
+    }
+
+    /// Renders the log as one timestamped line per event.
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        for event in &self.events {
+            writeln!(
+                output,
+                "[{}] {}",
+                format_time(event.session_time),
+                self.describe(event)
+            )
+            .unwrap();
+        }
+        output
+    }
+}
+
+fn is_running(lap: &LapData) -> bool {
+    match lap.result_status {
+        ResultStatus::Active | ResultStatus::Finished => lap.car_position > 0,
+        _ => false,
+    }
+}
+
+/// Formats seconds as `m:ss.mmm`.
+pub(crate) fn format_time(seconds: f32) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u32;
+    format!(
+        "{}:{:02}.{:03}",
+        millis / 60_000,
+        (millis / 1000) % 60,
+        millis % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{
+        event_packet, lap_data, lap_packet, participant, participants_packet,
+    };
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0.0), "0:00.000");
+        assert_eq!(format_time(83.456), "1:23.456");
+        assert_eq!(format_time(3725.5), "62:05.500");
+    }
+
+    #[test]
+    fn test_render_timeline() {
+        let mut log = EventLog::new();
+        log.update(&participants_packet(
+            1,
+            vec![
+                participant("HAMILTON", TeamId::Mercedes, 44),
+                participant("VETTEL", TeamId::Ferrari, 5),
+            ],
+        ));
+        log.update(&event_packet(
+            1,
+            0.0,
+            0,
+            "SSTA",
+            EventDataDetails::SessionStarted,
+        ));
+        log.update(&lap_packet(
+            1,
+            60.0,
+            100,
+            vec![lap_data(1, 1, 900.0), lap_data(2, 1, 880.0)],
+        ));
+
+        let mut second = lap_data(1, 1, 1010.0);
+        second.penalties = 5;
+        log.update(&lap_packet(
+            1,
+            65.0,
+            110,
+            vec![lap_data(2, 1, 1000.0), second],
+        ));
+        log.update(&event_packet(
+            1,
+            90.5,
+            150,
+            "FTLP",
+            EventDataDetails::FastestLap {
+                vehicle_index: 1,
+                lap_time: 88.2,
+            },
+        ));
+
+        assert_eq!(
+            log.render(),
+            "[0:00.000] Session started\n\
+             [1:05.000] #5 VETTEL (Ferrari) overtook #44 HAMILTON (Mercedes) on lap 1\n\
+             [1:05.000] #5 VETTEL (Ferrari) received a 5s penalty (5s total)\n\
+             [1:30.500] Fastest lap: #5 VETTEL (Ferrari) 1:28.200\n"
+        );
+    }
+}
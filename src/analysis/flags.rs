@@ -0,0 +1,235 @@
+use crate::mappings::{FiaFlag, Flag, SafetyCarStatus};
+use crate::packets::MarshalZone;
+use crate::{Telemetry, TelemetryData};
+
+/// A period of session time with the same state. `end` is `None` while the
+/// state still holds.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Interval<T> {
+    pub value: T,
+    pub start: f32,
+    pub end: Option<f32>,
+}
+
+impl<T> Interval<T> {
+    /// Length of the interval, counting an open interval up to `now`.
+    pub fn duration(&self, now: f32) -> f32 {
+        self.end.unwrap_or(now) - self.start
+    }
+}
+
+/// Closes the open interval when the state changes and opens a new one for
+/// `value`, if any.
+fn record<T: Copy + PartialEq>(intervals: &mut Vec<Interval<T>>, value: Option<T>, time: f32) {
+    if let Some(last) = intervals.last_mut() {
+        if last.end.is_none() {
+            if Some(last.value) == value {
+                return;
+            }
+            last.end = Some(time);
+        }
+    }
+
+    if let Some(value) = value {
+        intervals.push(Interval {
+            value,
+            start: time,
+            end: None,
+        });
+    }
+}
+
+/// History of marshal zone flags, flags shown to each car and safety car
+/// periods.
+#[derive(Debug, Default)]
+pub struct FlagTracker {
+    track_length: u16,
+    zone_starts: Vec<f32>,
+    zones: Vec<Vec<Interval<Flag>>>,
+    cars: Vec<Vec<Interval<FiaFlag>>>,
+    safety_car: Vec<Interval<SafetyCarStatus>>,
+}
+
+impl FlagTracker {
+    pub fn new() -> Self {
+        FlagTracker::default()
+    }
+
+    pub fn update(&mut self, packet: &Telemetry) {
+        let time = packet.header.session_time;
+        match packet.data {
+            TelemetryData::Session(ref data) => {
+                self.track_length = data.track_length;
+                self.update_zones(time, &data.marshal_zones);
+                let safety_car = match data.safety_car_status {
+                    SafetyCarStatus::None => None,
+                    status => Some(status),
+                };
+                record(&mut self.safety_car, safety_car, time);
+            }
+            TelemetryData::CarStatus(ref data) => {
+                self.cars.resize_with(data.car_status_data.len(), Vec::new);
+                for (intervals, status) in self.cars.iter_mut().zip(&data.car_status_data) {
+                    let flag = match status.vehicle_fia_flags {
+                        FiaFlag::None | FiaFlag::Unknown => None,
+                        flag => Some(flag),
+                    };
+                    record(intervals, flag, time);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn update_zones(&mut self, time: f32, zones: &[MarshalZone]) {
+        self.zone_starts = zones.iter().map(|zone| zone.zone_start).collect();
+        self.zones.resize_with(zones.len(), Vec::new);
+        for (intervals, zone) in self.zones.iter_mut().zip(zones) {
+            let flag = match zone.zone_flag {
+                Flag::None | Flag::Unknown => None,
+                flag => Some(flag),
+            };
+            record(intervals, flag, time);
+        }
+    }
+
+    /// Ends every open interval, e.g. when the session is over.
+    pub fn close(&mut self, time: f32) {
+        for intervals in self.zones.iter_mut() {
+            record(intervals, None, time);
+        }
+        for intervals in self.cars.iter_mut() {
+            record(intervals, None, time);
+        }
+        record(&mut self.safety_car, None, time);
+    }
+
+    pub fn zone_intervals(&self, zone: usize) -> &[Interval<Flag>] {
+        self.zones.get(zone).map_or(&[], |intervals| &intervals[..])
+    }
+
+    pub fn car_intervals(&self, vehicle_index: u8) -> &[Interval<FiaFlag>] {
+        self.cars
+            .get(vehicle_index as usize)
+            .map_or(&[], |intervals| &intervals[..])
+    }
+
+    /// Full and virtual safety car periods.
+    pub fn safety_car_periods(&self) -> &[Interval<SafetyCarStatus>] {
+        &self.safety_car
+    }
+
+    /// Total time a zone has shown `flag`, counting open intervals up to `now`.
+    pub fn time_under(&self, zone: usize, flag: Flag, now: f32) -> f32 {
+        self.zone_intervals(zone)
+            .iter()
+            .filter(|interval| interval.value == flag)
+            .map(|interval| interval.duration(now))
+            .sum()
+    }
+
+    /// Cars that have been shown a blue flag at some point.
+    pub fn blue_flagged(&self) -> Vec<u8> {
+        self.cars
+            .iter()
+            .enumerate()
+            .filter(|(_, intervals)| {
+                intervals
+                    .iter()
+                    .any(|interval| interval.value == FiaFlag::Blue)
+            })
+            .map(|(vehicle, _)| vehicle as u8)
+            .collect()
+    }
+
+    /// The `lap_distance` range covered by each marshal zone, from its start
+    /// to the start of the next zone or the end of the lap.
+    pub fn zone_ranges(&self) -> Vec<(f32, f32)> {
+        let track_length = self.track_length as f32;
+        self.zone_starts
+            .iter()
+            .enumerate()
+            .map(|(zone, start)| {
+                let end = self.zone_starts.get(zone + 1).copied().unwrap_or(1.0);
+                (start * track_length, end * track_length)
+            })
+            .collect()
+    }
+
+    /// The marshal zone containing a point on the lap.
+    pub fn zone_at(&self, lap_distance: f32) -> Option<usize> {
+        self.zone_ranges()
+            .iter()
+            .position(|(start, end)| lap_distance >= *start && lap_distance < *end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{
+        car_status, car_status_packet, compound, session_data, session_packet,
+    };
+    use crate::mappings::{SessionType, TrackId};
+
+    #[test]
+    fn test_zone_and_safety_car_intervals() {
+        let mut tracker = FlagTracker::new();
+        let mut session = session_data(SessionType::Race, TrackId::Monza);
+        session.track_length = 5000;
+        session.marshal_zones = vec![
+            MarshalZone {
+                zone_start: 0.0,
+                zone_flag: Flag::Green,
+            },
+            MarshalZone {
+                zone_start: 0.4,
+                zone_flag: Flag::None,
+            },
+        ];
+        tracker.update(&session_packet(1, 0.0, 0, session.clone()));
+
+        session.marshal_zones[1].zone_flag = Flag::Yellow;
+        session.safety_car_status = SafetyCarStatus::Virtual;
+        tracker.update(&session_packet(1, 10.0, 1, session.clone()));
+        session.marshal_zones[1].zone_flag = Flag::None;
+        tracker.update(&session_packet(1, 25.0, 2, session.clone()));
+        session.marshal_zones[1].zone_flag = Flag::Yellow;
+        session.safety_car_status = SafetyCarStatus::None;
+        tracker.update(&session_packet(1, 40.0, 3, session));
+
+        assert_eq!(tracker.zone_intervals(0).len(), 1);
+        assert_eq!(tracker.time_under(1, Flag::Yellow, 50.0), 25.0);
+        assert_eq!(
+            tracker.safety_car_periods(),
+            &[Interval {
+                value: SafetyCarStatus::Virtual,
+                start: 10.0,
+                end: Some(40.0),
+            }]
+        );
+
+        tracker.close(60.0);
+        assert_eq!(tracker.zone_intervals(1)[1].end, Some(60.0));
+        assert_eq!(tracker.zone_ranges(), vec![(0.0, 2000.0), (2000.0, 5000.0)]);
+        assert_eq!(tracker.zone_at(2500.0), Some(1));
+    }
+
+    #[test]
+    fn test_car_flags() {
+        let mut tracker = FlagTracker::new();
+        let statuses = |flag| {
+            let mut lapped = car_status(compound(18));
+            lapped.vehicle_fia_flags = flag;
+            vec![car_status(compound(18)), lapped]
+        };
+
+        tracker.update(&car_status_packet(1, 0.0, 0, statuses(FiaFlag::None)));
+        tracker.update(&car_status_packet(1, 5.0, 1, statuses(FiaFlag::Blue)));
+        tracker.update(&car_status_packet(1, 8.0, 2, statuses(FiaFlag::None)));
+
+        assert!(tracker.car_intervals(0).is_empty());
+        assert_eq!(tracker.car_intervals(1)[0].duration(100.0), 3.0);
+        assert_eq!(tracker.blue_flagged(), vec![1]);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::Telemetry;
+
+/// Packets arriving this far (in seconds) behind the latest packet are treated
+/// as a flashback rather than UDP reordering.
+const REWIND_TOLERANCE: f32 = 0.5;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Rewind {
+    pub session_uid: u64,
+    pub from_time: f32,
+    pub to_time: f32,
+    pub from_frame: u32,
+    pub to_frame: u32,
+}
+
+impl Rewind {
+    /// Seconds of session time that were undone by the flashback.
+    pub fn duration(&self) -> f32 {
+        self.from_time - self.to_time
+    }
+}
+
+/// Detects `session_time` and `frame_identifier` jumping backwards.
+#[derive(Debug, Default)]
+pub struct FlashbackDetector {
+    last: Option<(u64, f32, u32)>,
+}
+
+impl FlashbackDetector {
+    pub fn new() -> Self {
+        FlashbackDetector::default()
+    }
+
+    pub fn push(&mut self, packet: &Telemetry) -> Option<Rewind> {
+        let uid = packet.header.session_uid;
+        let time = packet.header.session_time;
+        let frame = packet.header.frame_identifier;
+
+        let rewind = match self.last {
+            Some((last_uid, last_time, last_frame))
+                if last_uid == uid && time < last_time - REWIND_TOLERANCE && frame < last_frame =>
+            {
+                Some(Rewind {
+                    session_uid: uid,
+                    from_time: last_time,
+                    to_time: time,
+                    from_frame: last_frame,
+                    to_frame: frame,
+                })
+            }
+            _ => None,
+        };
+
+        match self.last {
+            Some((last_uid, last_time, _)) if last_uid == uid && rewind.is_none() => {
+                if time > last_time {
+                    self.last = Some((uid, time, frame));
+                }
+            }
+            _ => self.last = Some((uid, time, frame)),
+        }
+
+        rewind
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FlashbackMode {
+    /// Keep every packet, including the timeline that was rewound.
+    Keep,
+    /// Drop packets from the timeline that a flashback replaced.
+    Discard,
+}
+
+/// A packet history that can optionally repair itself after flashbacks.
+#[derive(Debug)]
+pub struct Timeline<'a> {
+    mode: FlashbackMode,
+    detector: FlashbackDetector,
+    packets: Vec<Telemetry<'a>>,
+    rewinds: Vec<Rewind>,
+}
+
+impl<'a> Timeline<'a> {
+    pub fn new(mode: FlashbackMode) -> Self {
+        Timeline {
+            mode,
+            detector: FlashbackDetector::new(),
+            packets: Vec::new(),
+            rewinds: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, packet: Telemetry<'a>) -> Option<Rewind> {
+        let rewind = self.detector.push(&packet);
+
+        if let Some(rewind) = rewind {
+            if self.mode == FlashbackMode::Discard {
+                // Reordered packets can leave superseded ones anywhere after
+                // the rewind target, not just at the end.
+                self.packets.retain(|packet| {
+                    packet.header.session_uid != rewind.session_uid
+                        || packet.header.session_time < rewind.to_time
+                });
+            }
+            self.rewinds.push(rewind);
+        }
+
+        self.packets.push(packet);
+        rewind
+    }
+
+    pub fn packets(&self) -> &[Telemetry<'a>] {
+        &self.packets
+    }
+
+    pub fn rewinds(&self) -> &[Rewind] {
+        &self.rewinds
+    }
+
+    pub fn into_packets(self) -> Vec<Telemetry<'a>> {
+        self.packets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{session_data, session_packet};
+    use crate::mappings::{SessionType, TrackId};
+
+    fn packet(session_uid: u64, session_time: f32, frame: u32) -> Telemetry<'static> {
+        let data = session_data(SessionType::Race, TrackId::Suzuka);
+        session_packet(session_uid, session_time, frame, data)
+    }
+
+    #[test]
+    fn test_detect_rewind() {
+        let mut detector = FlashbackDetector::new();
+
+        assert_eq!(detector.push(&packet(1, 10.0, 100)), None);
+        assert_eq!(detector.push(&packet(1, 9.8, 99)), None);
+        assert_eq!(detector.push(&packet(1, 20.0, 200)), None);
+
+        let rewind = detector.push(&packet(1, 15.0, 150)).unwrap();
+        assert_eq!(rewind.from_time, 20.0);
+        assert_eq!(rewind.to_time, 15.0);
+        assert_eq!(rewind.duration(), 5.0);
+
+        assert_eq!(detector.push(&packet(2, 1.0, 1)), None);
+    }
+
+    #[test]
+    fn test_discard_superseded_timeline() {
+        let times = [
+            (10.0, 100),
+            (15.0, 150),
+            (20.0, 200),
+            (12.0, 120),
+            (13.0, 130),
+        ];
+
+        let mut keep = Timeline::new(FlashbackMode::Keep);
+        let mut discard = Timeline::new(FlashbackMode::Discard);
+        for &(time, frame) in times.iter() {
+            keep.push(packet(1, time, frame));
+            discard.push(packet(1, time, frame));
+        }
+
+        assert_eq!(keep.packets().len(), 5);
+        assert_eq!(keep.rewinds().len(), 1);
+
+        let remaining: Vec<f32> = discard
+            .packets()
+            .iter()
+            .map(|packet| packet.header.session_time)
+            .collect();
+        assert_eq!(remaining, vec![10.0, 12.0, 13.0]);
+        assert_eq!(discard.rewinds().len(), 1);
+    }
+
+    #[test]
+    fn test_discard_reordered_packets() {
+        let mut discard = Timeline::new(FlashbackMode::Discard);
+        for &(time, frame) in [
+            (10.0, 100),
+            (13.0, 130),
+            (12.9, 129),
+            (20.0, 200),
+            (12.95, 129),
+        ]
+        .iter()
+        {
+            discard.push(packet(1, time, frame));
+        }
+
+        let remaining: Vec<f32> = discard
+            .packets()
+            .iter()
+            .map(|packet| packet.header.session_time)
+            .collect();
+        assert_eq!(remaining, vec![10.0, 12.9, 12.95]);
+    }
+}
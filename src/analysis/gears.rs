@@ -0,0 +1,387 @@
+use crate::analysis::stats::Summary;
+use crate::packets::CarTelemetryData;
+use crate::{Telemetry, TelemetryData};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GearConfig {
+    /// Throttle travel, between 0 and 1, from which a sample counts towards
+    /// the acceleration curves.
+    pub full_throttle: f32,
+    /// Width of the speed bins the acceleration curves are kept in, in km/h.
+    pub bin_width: u16,
+    /// Samples needed in a speed bin before it is trusted.
+    pub minimum_samples: u32,
+    /// Fraction of `max_rpm` kept in hand when shifting at the limiter.
+    pub rev_limit_margin: f32,
+}
+
+impl Default for GearConfig {
+    fn default() -> Self {
+        GearConfig {
+            full_throttle: 0.95,
+            bin_width: 5,
+            minimum_samples: 3,
+            rev_limit_margin: 0.02,
+        }
+    }
+}
+
+/// A change between two forward gears.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Shift {
+    pub lap: u8,
+    pub session_time: f32,
+    pub lap_distance: f32,
+    pub from: i8,
+    pub to: i8,
+    pub rpm_before: u16,
+    pub rpm_after: u16,
+    /// Speed in km/h.
+    pub speed: u16,
+}
+
+impl Shift {
+    pub fn is_upshift(&self) -> bool {
+        self.to > self.from
+    }
+}
+
+/// Engine speed and acceleration seen in one gear.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GearCurve {
+    pub gear: i8,
+    pub speed: Summary,
+    pub rpm: Summary,
+    /// Engine speed per km/h of road speed.
+    pub ratio: Summary,
+    pub bin_width: u16,
+    /// Full throttle acceleration in m/s², by speed bin starting at 0 km/h.
+    pub acceleration: Vec<Summary>,
+}
+
+impl GearCurve {
+    fn new(gear: i8, bin_width: u16) -> Self {
+        GearCurve {
+            gear,
+            speed: Summary::default(),
+            rpm: Summary::default(),
+            ratio: Summary::default(),
+            bin_width,
+            acceleration: Vec::new(),
+        }
+    }
+
+    /// Engine speed at `speed` km/h in this gear.
+    pub fn rpm_at(&self, speed: f32) -> Option<f32> {
+        Some(self.ratio.mean()? * speed)
+    }
+
+    /// Mean full throttle acceleration in the speed bin containing `speed`.
+    pub fn acceleration_at(&self, speed: f32, minimum_samples: u32) -> Option<f32> {
+        let bin = self
+            .acceleration
+            .get((speed / self.bin_width as f32) as usize)?;
+        if bin.count < minimum_samples {
+            return None;
+        }
+        bin.mean()
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ShiftBasis {
+    /// The next gear accelerates harder from this speed.
+    Acceleration,
+    /// No crossover was seen, so the shift is made just short of `max_rpm`.
+    RevLimit,
+}
+
+/// Recommended upshift out of a gear.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShiftPoint {
+    pub from: i8,
+    /// Speed in km/h.
+    pub speed: f32,
+    pub rpm: f32,
+    pub basis: ShiftBasis,
+    /// Mean engine speed the player actually shifted up from this gear at.
+    pub actual_rpm: Option<f32>,
+}
+
+/// Detects the player's gear shifts and builds per gear speed, engine speed
+/// and acceleration curves to recommend shift points from.
+#[derive(Debug, Default)]
+pub struct GearAnalyzer {
+    config: GearConfig,
+    max_rpm: Option<u16>,
+    idle_rpm: Option<u16>,
+    max_gears: Option<u8>,
+    lap: u8,
+    lap_distance: f32,
+    last: Option<(f32, CarTelemetryData)>,
+    shifts: Vec<Shift>,
+    /// Indexed by gear less one.
+    curves: Vec<GearCurve>,
+}
+
+impl GearAnalyzer {
+    pub fn new(config: GearConfig) -> Self {
+        GearAnalyzer {
+            config,
+            ..GearAnalyzer::default()
+        }
+    }
+
+    pub fn shifts(&self) -> &[Shift] {
+        &self.shifts
+    }
+
+    pub fn max_rpm(&self) -> Option<u16> {
+        self.max_rpm
+    }
+
+    pub fn idle_rpm(&self) -> Option<u16> {
+        self.idle_rpm
+    }
+
+    pub fn curve(&self, gear: i8) -> Option<&GearCurve> {
+        self.curves
+            .get((gear as usize).wrapping_sub(1))
+            .filter(|curve| curve.speed.count > 0)
+    }
+
+    /// Returns the shift made in this packet, if any.
+    pub fn update(&mut self, packet: &Telemetry) -> Option<&Shift> {
+        let player = packet.header.player_car_index as usize;
+        let session_time = packet.header.session_time;
+
+        match packet.data {
+            TelemetryData::Lap(ref data) => {
+                if let Some(lap) = data.lap_data.get(player) {
+                    self.lap = lap.current_lap_num;
+                    self.lap_distance = lap.lap_distance;
+                }
+            }
+            TelemetryData::CarStatus(ref data) => {
+                if let Some(status) = data.car_status_data.get(player) {
+                    self.max_rpm = Some(status.max_rpm);
+                    self.idle_rpm = Some(status.idle_rpm);
+                    self.max_gears = Some(status.max_gears);
+                }
+            }
+            TelemetryData::CarTelemetry(ref data) => {
+                if let Some(telemetry) = data.car_telemetry_data.get(player) {
+                    if self.update_telemetry(session_time, telemetry) {
+                        return self.shifts.last();
+                    }
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Returns true when the player has just changed gear.
+    fn update_telemetry(&mut self, session_time: f32, telemetry: &CarTelemetryData) -> bool {
+        let last = self.last.replace((session_time, *telemetry));
+        if telemetry.gear <= 0 {
+            return false;
+        }
+
+        let config = self.config;
+        let index = telemetry.gear as usize - 1;
+        if self.curves.len() <= index {
+            let gears = self.curves.len() as i8;
+            self.curves.extend(
+                (gears + 1..=telemetry.gear).map(|gear| GearCurve::new(gear, config.bin_width)),
+            );
+        }
+        let curve = &mut self.curves[index];
+        curve.speed.add(telemetry.speed as f32);
+        curve.rpm.add(telemetry.engine_rpm as f32);
+        if telemetry.speed > 0 {
+            curve
+                .ratio
+                .add(telemetry.engine_rpm as f32 / telemetry.speed as f32);
+        }
+
+        let (last_time, previous) = match last {
+            Some(last) => last,
+            None => return false,
+        };
+
+        if previous.gear == telemetry.gear {
+            let dt = session_time - last_time;
+            if dt > 0.0
+                && previous.throttle >= config.full_throttle
+                && telemetry.throttle >= config.full_throttle
+            {
+                let acceleration = (telemetry.speed as f32 - previous.speed as f32) / 3.6 / dt;
+                let bin = (previous.speed / config.bin_width) as usize;
+                if curve.acceleration.len() <= bin {
+                    curve.acceleration.resize_with(bin + 1, Summary::default);
+                }
+                curve.acceleration[bin].add(acceleration);
+            }
+            return false;
+        }
+
+        if previous.gear <= 0 {
+            return false;
+        }
+        self.shifts.push(Shift {
+            lap: self.lap,
+            session_time,
+            lap_distance: self.lap_distance,
+            from: previous.gear,
+            to: telemetry.gear,
+            rpm_before: previous.engine_rpm,
+            rpm_after: telemetry.engine_rpm,
+            speed: previous.speed,
+        });
+        true
+    }
+
+    /// Mean engine speed of the player's upshifts out of `gear`.
+    fn actual_rpm(&self, gear: i8) -> Option<f32> {
+        let mut rpm = Summary::default();
+        for shift in self
+            .shifts
+            .iter()
+            .filter(|s| s.from == gear && s.is_upshift())
+        {
+            rpm.add(shift.rpm_before as f32);
+        }
+        rpm.mean()
+    }
+
+    /// Upshift points for every gear with data, below the top gear.
+    pub fn recommendations(&self) -> Vec<ShiftPoint> {
+        let top = self
+            .max_gears
+            .map_or(self.curves.len() as i8, |gears| gears as i8);
+        (1..top)
+            .filter_map(|gear| self.recommendation(gear))
+            .collect()
+    }
+
+    fn recommendation(&self, gear: i8) -> Option<ShiftPoint> {
+        let curve = self.curve(gear)?;
+        let minimum_samples = self.config.minimum_samples;
+        let crossover = self.curve(gear + 1).and_then(|next| {
+            (0..curve.acceleration.len())
+                .map(|bin| (bin as f32 + 0.5) * curve.bin_width as f32)
+                .find(|&speed| {
+                    match (
+                        curve.acceleration_at(speed, minimum_samples),
+                        next.acceleration_at(speed, minimum_samples),
+                    ) {
+                        (Some(current), Some(next)) => next >= current,
+                        _ => false,
+                    }
+                })
+        });
+
+        let limit = self.max_rpm? as f32 * (1.0 - self.config.rev_limit_margin);
+        let (speed, rpm, basis) = match crossover {
+            Some(speed) if curve.rpm_at(speed)? < limit => {
+                (speed, curve.rpm_at(speed)?, ShiftBasis::Acceleration)
+            }
+            _ => (limit / curve.ratio.mean()?, limit, ShiftBasis::RevLimit),
+        };
+
+        Some(ShiftPoint {
+            from: gear,
+            speed,
+            rpm,
+            basis,
+            actual_rpm: self.actual_rpm(gear),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{
+        car_status, car_status_packet, car_telemetry, car_telemetry_packet, compound,
+    };
+
+    /// Full throttle in `gear` from `from` to `to` km/h, with acceleration
+    /// falling off with speed. Returns the session time at the end.
+    fn accelerate(
+        analyzer: &mut GearAnalyzer,
+        mut time: f32,
+        gear: i8,
+        ratio: u16,
+        speeds: std::ops::Range<u16>,
+        acceleration: impl Fn(f32) -> f32,
+    ) -> f32 {
+        for speed in speeds {
+            let mut telemetry = car_telemetry(speed, 1.0, 0.0);
+            telemetry.gear = gear;
+            telemetry.engine_rpm = ratio * speed;
+            analyzer.update(&car_telemetry_packet(1, time, 0, vec![telemetry]));
+            time += 1.0 / 3.6 / acceleration(speed as f32);
+        }
+        time
+    }
+
+    #[test]
+    fn test_shifts_and_curves() {
+        let mut analyzer = GearAnalyzer::default();
+        let time = accelerate(&mut analyzer, 0.0, 3, 80, 100..110, |_| 5.0);
+        let time = accelerate(&mut analyzer, time, 4, 60, 110..120, |_| 4.0);
+
+        let mut telemetry = car_telemetry(90, 0.0, 1.0);
+        telemetry.gear = 2;
+        let shift = *analyzer
+            .update(&car_telemetry_packet(1, time, 0, vec![telemetry]))
+            .unwrap();
+        assert_eq!((shift.from, shift.to), (4, 2));
+        assert!(!shift.is_upshift());
+
+        let shifts = analyzer.shifts();
+        assert_eq!(shifts.len(), 2);
+        assert_eq!((shifts[0].rpm_before, shifts[0].rpm_after), (8720, 6600));
+        assert_eq!(shifts[0].speed, 109);
+
+        let third = analyzer.curve(3).unwrap();
+        assert_eq!(third.ratio.mean(), Some(80.0));
+        assert_eq!(third.speed.max, 109.0);
+        let acceleration = third.acceleration_at(102.0, 3).unwrap();
+        assert!((acceleration - 5.0).abs() < 1e-3);
+        assert!(analyzer.curve(1).is_none());
+        assert!(analyzer.curve(0).is_none());
+    }
+
+    #[test]
+    fn test_recommendations() {
+        let mut analyzer = GearAnalyzer::default();
+        analyzer.update(&car_status_packet(
+            1,
+            0.0,
+            0,
+            vec![car_status(compound(16))],
+        ));
+
+        // First gear pulls harder at low speed, second from 100 km/h.
+        let time = accelerate(&mut analyzer, 0.0, 1, 100, 60..115, |s| 10.0 - 0.05 * s);
+        accelerate(&mut analyzer, time, 2, 50, 80..200, |s| 7.0 - 0.02 * s);
+
+        let points = analyzer.recommendations();
+        assert_eq!(points.len(), 2);
+
+        let first = points[0];
+        assert_eq!(first.basis, ShiftBasis::Acceleration);
+        assert_eq!(first.speed, 102.5);
+        assert!((first.rpm - 10250.0).abs() < 1.0);
+        assert_eq!(first.actual_rpm, Some(11400.0));
+
+        let second = points[1];
+        assert_eq!(second.basis, ShiftBasis::RevLimit);
+        assert!((second.rpm - 11760.0).abs() < 1.0);
+        assert!((second.speed - 235.2).abs() < 0.1);
+        assert_eq!(second.actual_rpm, None);
+    }
+}
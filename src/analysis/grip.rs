@@ -0,0 +1,271 @@
+use crate::analysis::brakes::braking_slip;
+use crate::packets::MotionData;
+use crate::{Telemetry, TelemetryData, Wheel};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GripThresholds {
+    /// Wheel slip ratio beyond which a wheel is locked (negative) or
+    /// spinning (positive).
+    pub slip: f32,
+    /// Throttle, between 0 and 1, needed for positive slip to count as
+    /// wheelspin.
+    pub wheelspin_throttle: f32,
+    /// Forward speed, in metres per second, below which nothing is reported.
+    pub minimum_speed: f32,
+    /// Distance between the axles in metres, used to work out the yaw rate
+    /// the steering input should produce.
+    pub wheelbase: f32,
+    /// Ratio of actual to expected yaw rate below which the car understeers.
+    pub understeer_ratio: f32,
+    /// Ratio of actual to expected yaw rate above which the car oversteers.
+    pub oversteer_ratio: f32,
+    /// Yaw rates, in radians per second, smaller than this are ignored.
+    pub minimum_yaw_rate: f32,
+}
+
+impl Default for GripThresholds {
+    fn default() -> Self {
+        GripThresholds {
+            slip: 0.2,
+            wheelspin_throttle: 0.2,
+            minimum_speed: 10.0,
+            wheelbase: 3.6,
+            understeer_ratio: 0.7,
+            oversteer_ratio: 1.3,
+            minimum_yaw_rate: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GripEventKind {
+    LockUp(Wheel),
+    Wheelspin(Wheel),
+    Understeer,
+    Oversteer,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GripEvent {
+    pub kind: GripEventKind,
+    pub lap: u8,
+    /// Lap distance where the event started.
+    pub lap_distance: f32,
+    pub session_time: f32,
+    pub duration: f32,
+    /// Largest slip ratio for wheel events, or yaw rate ratio for handling
+    /// events.
+    pub peak: f32,
+}
+
+/// Finds lock-ups, wheelspin, understeer and oversteer of the player car.
+///
+/// Events are reported once they are over, so their duration is known.
+#[derive(Debug, Default)]
+pub struct GripEventDetector {
+    thresholds: GripThresholds,
+    lap: u8,
+    lap_distance: f32,
+    throttle: f32,
+    active: Vec<GripEvent>,
+    events: Vec<GripEvent>,
+}
+
+impl GripEventDetector {
+    pub fn new(thresholds: GripThresholds) -> Self {
+        GripEventDetector {
+            thresholds,
+            ..GripEventDetector::default()
+        }
+    }
+
+    pub fn events(&self) -> &[GripEvent] {
+        &self.events
+    }
+
+    /// Returns the events that ended with this packet.
+    pub fn update(&mut self, packet: &Telemetry) -> Vec<GripEvent> {
+        let player = packet.header.player_car_index as usize;
+
+        match packet.data {
+            TelemetryData::Lap(ref data) => {
+                if let Some(lap) = data.lap_data.get(player) {
+                    self.lap = lap.current_lap_num;
+                    self.lap_distance = lap.lap_distance;
+                }
+                Vec::new()
+            }
+            TelemetryData::CarTelemetry(ref data) => {
+                if let Some(telemetry) = data.car_telemetry_data.get(player) {
+                    self.throttle = telemetry.throttle;
+                }
+                Vec::new()
+            }
+            TelemetryData::Motion(ref data) => {
+                let detected = self.detect(data);
+                self.track(packet.header.session_time, &detected)
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Everything the car is doing wrong in this motion packet, with the
+    /// matching peak value.
+    fn detect(&self, data: &MotionData) -> Vec<(GripEventKind, f32)> {
+        let thresholds = &self.thresholds;
+        let mut detected = Vec::new();
+        let speed = data.local_velocity.z;
+        if speed < thresholds.minimum_speed {
+            return detected;
+        }
+
+        for &wheel in Wheel::ALL.iter() {
+            let locking = braking_slip(data, wheel, speed);
+            let slip = *data.wheel_slip.get(wheel);
+            if locking <= -thresholds.slip {
+                detected.push((GripEventKind::LockUp(wheel), -locking));
+            } else if slip >= thresholds.slip
+                && !wheel.is_front()
+                && self.throttle >= thresholds.wheelspin_throttle
+            {
+                detected.push((GripEventKind::Wheelspin(wheel), slip));
+            }
+        }
+
+        let expected = speed * data.front_wheels_angle.tan() / thresholds.wheelbase;
+        let actual = data.angular_velocity.y;
+        if expected.abs() >= thresholds.minimum_yaw_rate {
+            let ratio = actual / expected;
+            if ratio < thresholds.understeer_ratio {
+                // A negative ratio means the car rotates against the steering,
+                // i.e. the driver is catching a slide.
+                if ratio < 0.0 && actual.abs() >= thresholds.minimum_yaw_rate {
+                    detected.push((GripEventKind::Oversteer, ratio.abs()));
+                } else {
+                    detected.push((GripEventKind::Understeer, ratio));
+                }
+            } else if ratio > thresholds.oversteer_ratio {
+                detected.push((GripEventKind::Oversteer, ratio));
+            }
+        }
+
+        detected
+    }
+
+    fn track(&mut self, session_time: f32, detected: &[(GripEventKind, f32)]) -> Vec<GripEvent> {
+        let mut ended = Vec::new();
+        let mut index = 0;
+        while index < self.active.len() {
+            let event = &mut self.active[index];
+            match detected.iter().find(|(kind, _)| *kind == event.kind) {
+                Some(&(_, peak)) => {
+                    event.peak = match event.kind {
+                        GripEventKind::Understeer => event.peak.min(peak),
+                        _ => event.peak.max(peak),
+                    };
+                    index += 1;
+                }
+                None => {
+                    let mut event = self.active.remove(index);
+                    event.duration = session_time - event.session_time;
+                    ended.push(event);
+                }
+            }
+        }
+
+        for &(kind, peak) in detected {
+            if !self.active.iter().any(|event| event.kind == kind) {
+                self.active.push(GripEvent {
+                    kind,
+                    lap: self.lap,
+                    lap_distance: self.lap_distance,
+                    session_time,
+                    duration: 0.0,
+                    peak,
+                });
+            }
+        }
+
+        self.events.extend_from_slice(&ended);
+        ended
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{
+        car_motion, car_telemetry, car_telemetry_packet, coordinates, lap_data, lap_packet,
+        motion_data, motion_packet, wheels,
+    };
+
+    fn motion(speed: f32, steer: f32, yaw_rate: f32) -> MotionData {
+        let mut data = motion_data(vec![car_motion(0.0, 0.0)]);
+        data.local_velocity = coordinates(0.0, 0.0, speed);
+        data.wheel_speed = wheels(speed);
+        data.front_wheels_angle = steer;
+        data.angular_velocity = coordinates(0.0, yaw_rate, 0.0);
+        data
+    }
+
+    #[test]
+    fn test_lock_up_and_wheelspin() {
+        let mut detector = GripEventDetector::default();
+        detector.update(&lap_packet(1, 0.0, 0, vec![lap_data(1, 3, 750.0)]));
+
+        // Rear slip off the throttle is not wheelspin.
+        let mut data = motion(50.0, 0.0, 0.0);
+        data.wheel_slip.rear_right = 0.4;
+        detector.update(&motion_packet(1, 0.5, 0, data));
+        assert!(detector
+            .update(&motion_packet(1, 0.6, 0, motion(50.0, 0.0, 0.0)))
+            .is_empty());
+
+        detector.update(&car_telemetry_packet(
+            1,
+            0.9,
+            0,
+            vec![car_telemetry(180, 1.0, 0.0)],
+        ));
+        let mut data = motion(50.0, 0.0, 0.0);
+        data.wheel_slip.front_left = -0.5;
+        data.wheel_slip.front_right = 0.5;
+        data.wheel_slip.rear_left = 0.3;
+        assert!(detector
+            .update(&motion_packet(1, 1.0, 1, data.clone()))
+            .is_empty());
+
+        data.wheel_slip.front_left = -0.8;
+        detector.update(&motion_packet(1, 1.1, 2, data));
+
+        let ended = detector.update(&motion_packet(1, 1.3, 3, motion(50.0, 0.0, 0.0)));
+        assert_eq!(ended.len(), 2);
+        assert_eq!(ended[0].kind, GripEventKind::Wheelspin(Wheel::RearLeft));
+        assert_eq!(ended[1].kind, GripEventKind::LockUp(Wheel::FrontLeft));
+        assert_eq!(ended[1].lap, 3);
+        assert_eq!(ended[1].lap_distance, 750.0);
+        assert_eq!(ended[1].peak, 0.8);
+        assert!((ended[1].duration - 0.3).abs() < 1e-6);
+        assert_eq!(detector.events().len(), 2);
+    }
+
+    #[test]
+    fn test_understeer_and_oversteer() {
+        let mut detector = GripEventDetector::default();
+        // 36 m/s with 0.1 rad of lock on a 3.6 m wheelbase asks for about
+        // 1 rad/s of yaw.
+        detector.update(&motion_packet(1, 0.0, 0, motion(36.0, 0.1, 0.5)));
+        let ended = detector.update(&motion_packet(1, 0.5, 1, motion(36.0, 0.1, 1.0)));
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0].kind, GripEventKind::Understeer);
+
+        detector.update(&motion_packet(1, 1.0, 2, motion(36.0, -0.02, 0.6)));
+        let ended = detector.update(&motion_packet(1, 1.5, 3, motion(36.0, 0.0, 0.0)));
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0].kind, GripEventKind::Oversteer);
+
+        assert!(detector
+            .update(&motion_packet(1, 2.0, 4, motion(5.0, 0.3, 0.0)))
+            .is_empty());
+    }
+}
@@ -0,0 +1,75 @@
+pub use self::alerts::{Alert, AlertConfig, AlertEngine, AlertMessage};
+pub use self::brakes::{BrakeAnalyzer, BrakingThresholds, BrakingZone, LockUp};
+pub use self::championship::{
+    Championship, ConstructorStanding, DriverStanding, PointsSystem, RaceEntry, RaceResult,
+};
+pub use self::classification::{Classification, ClassificationBuilder, ClassifiedDriver, Gap};
+pub use self::corners::{
+    Corner, CornerAnalyzer, CornerConfig, CornerMap, CornerPass, CornerSample, Direction,
+};
+pub use self::damage::{DamageCause, DamageComponent, DamageConfig, DamageIncrease, DamageTracker};
+pub use self::degradation::{DegradationCurve, DegradationLibrary, DegradationModel};
+pub use self::driving::{Consistency, DrivingAnalyzer, DrivingLap, DrivingThresholds, ShiftCounts};
+pub use self::event_log::{Driver, EventLog, RaceEvent, RaceEventKind};
+pub use self::flags::{FlagTracker, Interval};
+pub use self::flashback::{FlashbackDetector, FlashbackMode, Rewind, Timeline};
+pub use self::gears::{GearAnalyzer, GearConfig, GearCurve, Shift, ShiftBasis, ShiftPoint};
+pub use self::grip::{GripEvent, GripEventDetector, GripEventKind, GripThresholds};
+pub use self::overtakes::{Overtake, OvertakeDetector, PositionChange, PositionChangeCause};
+pub use self::proximity::{
+    distances, CarDimensions, OrientedBox, ProximityConfig, ProximityDetector, ProximityEvent,
+    ProximityKind, Side, Spotter,
+};
+pub use self::qualifying::{GridDifference, GridSlot, Knockout, QualifyingTracker};
+pub use self::safety_car::{CarPeriod, SafetyCarAnalyzer, SafetyCarPeriod};
+pub use self::sessions::{
+    split_sessions, BoundaryReason, Routing, Session, SessionDemultiplexer, SessionInfo,
+};
+pub use self::setups::{
+    diff_setups, validate_setup, SetupChangeDetector, SetupDifference, SetupField, SetupLibrary,
+    SetupViolation, SETUP_FIELDS,
+};
+pub use self::stats::{linear_fit, pearson, Summary};
+pub use self::stewarding::{Infringement, InfringementKind, StewardingTracker};
+pub use self::strategy::{
+    evaluate, overcut, pit_loss, rank_strategies, undercut, CarState, CompoundModel, PitBattle,
+    RaceState, Stint, StintLap, StintTracker, Strategy, StrategyModel, DEFAULT_FUEL_EFFECT,
+};
+pub use self::suspension::{
+    correlate, Attitude, Bottoming, CornerPhase, Histogram, SetupRun, SuspensionAnalyzer,
+    SuspensionConfig, SuspensionStats,
+};
+pub use self::time_trial::{CompletedLap, LapArchive, RecordedLap, TimeTrialRecorder, TraceSample};
+pub use self::tyres::{
+    Imbalance, LapTyres, OverheatAlert, TemperatureWindow, TyreAnalyzer, TyreStats, TyreWindows,
+};
+pub use self::weather::{Conditions, LapConditions, TyreCall, WeatherTracker, WeatherTransition};
+
+mod alerts;
+mod brakes;
+mod championship;
+mod classification;
+mod corners;
+mod damage;
+mod degradation;
+mod driving;
+mod event_log;
+mod flags;
+mod flashback;
+mod gears;
+mod grip;
+mod overtakes;
+mod proximity;
+mod qualifying;
+mod safety_car;
+mod sessions;
+mod setups;
+mod stats;
+mod stewarding;
+mod strategy;
+mod suspension;
+#[cfg(test)]
+mod test_support;
+mod time_trial;
+mod tyres;
+mod weather;
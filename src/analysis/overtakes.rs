@@ -0,0 +1,277 @@
+use crate::mappings::{PitStatus, ResultStatus};
+use crate::packets::LapData;
+use crate::{Telemetry, TelemetryData};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Overtake {
+    pub by: u8,
+    pub on: u8,
+    pub lap: u8,
+    pub lap_distance: f32,
+    pub session_time: f32,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PositionChangeCause {
+    PitStop,
+    Retirement,
+    Penalty,
+    /// The order changed without the cars passing each other on track or any
+    /// other recognisable cause.
+    Other,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PositionChange {
+    Overtake(Overtake),
+    /// `by` moved ahead of `on` without passing on track.
+    Reordered {
+        by: u8,
+        on: u8,
+        cause: PositionChangeCause,
+        session_time: f32,
+    },
+}
+
+/// Detects overtakes by comparing consecutive lap data packets.
+#[derive(Debug, Default)]
+pub struct OvertakeDetector {
+    previous: Vec<LapData>,
+}
+
+impl OvertakeDetector {
+    pub fn new() -> Self {
+        OvertakeDetector::default()
+    }
+
+    pub fn push(&mut self, packet: &Telemetry) -> Vec<PositionChange> {
+        match packet.data {
+            TelemetryData::Lap(ref data) => self.update(packet.header.session_time, &data.lap_data),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, session_time: f32, laps: &[LapData]) -> Vec<PositionChange> {
+        let previous = std::mem::replace(&mut self.previous, laps.to_vec());
+        let mut changes = Vec::new();
+
+        for (by, (by_before, by_now)) in previous.iter().zip(laps).enumerate() {
+            if !is_racing(by_before) || !is_racing(by_now) {
+                continue;
+            }
+
+            for (on, (on_before, on_now)) in previous.iter().zip(laps).enumerate() {
+                if by == on || !is_racing(on_before) || on_now.car_position == 0 {
+                    continue;
+                }
+
+                let passed = by_before.car_position > on_before.car_position
+                    && by_now.car_position < on_now.car_position;
+                if !passed {
+                    continue;
+                }
+
+                let (by, on) = (by as u8, on as u8);
+                let change = match cause(by_before, by_now, on_before, on_now) {
+                    None => PositionChange::Overtake(Overtake {
+                        by,
+                        on,
+                        lap: by_now.current_lap_num,
+                        lap_distance: by_now.lap_distance,
+                        session_time,
+                    }),
+                    Some(cause) => PositionChange::Reordered {
+                        by,
+                        on,
+                        cause,
+                        session_time,
+                    },
+                };
+                changes.push(change);
+            }
+        }
+
+        changes
+    }
+}
+
+fn is_racing(lap: &LapData) -> bool {
+    lap.result_status == ResultStatus::Active && lap.car_position > 0
+}
+
+/// Works out why `by` moved ahead of `on`, or `None` for an on-track pass.
+/// Either car's pit stop or penalty explains the change.
+fn cause(
+    by_before: &LapData,
+    by_now: &LapData,
+    on_before: &LapData,
+    on_now: &LapData,
+) -> Option<PositionChangeCause> {
+    match on_now.result_status {
+        ResultStatus::Retired | ResultStatus::Disqualified | ResultStatus::NotClassified => {
+            return Some(PositionChangeCause::Retirement)
+        }
+        _ => {}
+    }
+
+    let laps = [by_before, by_now, on_before, on_now];
+    if laps.iter().any(|lap| lap.pit_status != PitStatus::None) {
+        return Some(PositionChangeCause::PitStop);
+    }
+
+    // A penalty on `on`, or one on `by` being cleared, moves `by` ahead.
+    if on_now.penalties > on_before.penalties || by_now.penalties < by_before.penalties {
+        return Some(PositionChangeCause::Penalty);
+    }
+
+    if by_now.total_distance >= on_now.total_distance {
+        None
+    } else {
+        Some(PositionChangeCause::Other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::lap_data;
+
+    fn overtakes(changes: &[PositionChange]) -> Vec<(u8, u8)> {
+        changes
+            .iter()
+            .filter_map(|change| match change {
+                PositionChange::Overtake(overtake) => Some((overtake.by, overtake.on)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_on_track_pass() {
+        let mut detector = OvertakeDetector::new();
+        detector.update(
+            10.0,
+            &[
+                lap_data(1, 2, 6000.0),
+                lap_data(2, 2, 5990.0),
+                lap_data(3, 2, 5900.0),
+            ],
+        );
+        let changes = detector.update(
+            10.5,
+            &[
+                lap_data(2, 2, 6010.0),
+                lap_data(1, 2, 6015.0),
+                lap_data(3, 2, 5910.0),
+            ],
+        );
+
+        assert_eq!(changes.len(), 1);
+        match changes[0] {
+            PositionChange::Overtake(overtake) => {
+                assert_eq!(overtake.by, 1);
+                assert_eq!(overtake.on, 0);
+                assert_eq!(overtake.lap, 2);
+                assert_eq!(overtake.lap_distance, 1015.0);
+                assert_eq!(overtake.session_time, 10.5);
+            }
+            _ => panic!("expected an overtake"),
+        }
+    }
+
+    #[test]
+    fn test_pass_on_multiple_cars() {
+        let mut detector = OvertakeDetector::new();
+        detector.update(
+            0.0,
+            &[
+                lap_data(1, 1, 300.0),
+                lap_data(2, 1, 295.0),
+                lap_data(3, 1, 290.0),
+            ],
+        );
+        let changes = detector.update(
+            0.5,
+            &[
+                lap_data(2, 1, 305.0),
+                lap_data(3, 1, 300.0),
+                lap_data(1, 1, 310.0),
+            ],
+        );
+
+        assert_eq!(overtakes(&changes), vec![(2, 0), (2, 1)]);
+    }
+
+    #[test]
+    fn test_pit_stop_is_not_an_overtake() {
+        let mut detector = OvertakeDetector::new();
+        detector.update(0.0, &[lap_data(1, 5, 20000.0), lap_data(2, 5, 19900.0)]);
+
+        let mut pitting = lap_data(2, 5, 20010.0);
+        pitting.pit_status = PitStatus::InPitArea;
+        let changes = detector.update(20.0, &[pitting, lap_data(1, 5, 20000.0)]);
+
+        assert_eq!(
+            changes,
+            vec![PositionChange::Reordered {
+                by: 1,
+                on: 0,
+                cause: PositionChangeCause::PitStop,
+                session_time: 20.0,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_overtaking_car_pitting_is_not_an_overtake() {
+        let mut detector = OvertakeDetector::new();
+        detector.update(0.0, &[lap_data(1, 5, 20000.0), lap_data(2, 5, 19990.0)]);
+
+        let mut pitting = lap_data(1, 5, 20010.0);
+        pitting.pit_status = PitStatus::Pitting;
+        let changes = detector.update(0.5, &[lap_data(2, 5, 20005.0), pitting]);
+
+        match changes[..] {
+            [PositionChange::Reordered { by, cause, .. }] => {
+                assert_eq!(by, 1);
+                assert_eq!(cause, PositionChangeCause::PitStop);
+            }
+            _ => panic!("expected a pit stop"),
+        }
+    }
+
+    #[test]
+    fn test_retirement_is_not_an_overtake() {
+        let mut detector = OvertakeDetector::new();
+        detector.update(0.0, &[lap_data(1, 3, 11000.0), lap_data(2, 3, 10900.0)]);
+
+        let mut retired = lap_data(2, 3, 11000.0);
+        retired.result_status = ResultStatus::Retired;
+        let changes = detector.update(5.0, &[retired, lap_data(1, 3, 10950.0)]);
+
+        match changes[..] {
+            [PositionChange::Reordered { cause, .. }] => {
+                assert_eq!(cause, PositionChangeCause::Retirement)
+            }
+            _ => panic!("expected a retirement"),
+        }
+    }
+
+    #[test]
+    fn test_penalty_is_not_an_overtake() {
+        let mut detector = OvertakeDetector::new();
+        detector.update(0.0, &[lap_data(1, 5, 25000.0), lap_data(2, 5, 24990.0)]);
+
+        let mut penalised = lap_data(2, 5, 25010.0);
+        penalised.penalties = 5;
+        let changes = detector.update(0.5, &[penalised, lap_data(1, 5, 25000.0)]);
+
+        match changes[..] {
+            [PositionChange::Reordered { by, on, cause, .. }] => {
+                assert_eq!((by, on), (1, 0));
+                assert_eq!(cause, PositionChangeCause::Penalty);
+            }
+            _ => panic!("expected a penalty"),
+        }
+    }
+}
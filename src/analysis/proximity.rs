@@ -0,0 +1,326 @@
+use crate::packets::{CarMotionData, MotionData};
+use crate::{Telemetry, TelemetryData};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CarDimensions {
+    pub length: f32,
+    pub width: f32,
+}
+
+impl Default for CarDimensions {
+    fn default() -> Self {
+        CarDimensions {
+            length: 5.6,
+            width: 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ProximityConfig {
+    pub dimensions: CarDimensions,
+    /// Gap, in metres, between two cars below which they count as a near miss.
+    pub near_miss: f32,
+    /// Largest sideways gap, in metres, at which the spotter still calls a
+    /// car alongside.
+    pub spotter_range: f32,
+}
+
+impl Default for ProximityConfig {
+    fn default() -> Self {
+        ProximityConfig {
+            dimensions: CarDimensions::default(),
+            near_miss: 0.5,
+            spotter_range: 3.0,
+        }
+    }
+}
+
+fn dot(a: (f32, f32), b: (f32, f32)) -> f32 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+/// The footprint of a car on the ground, using the x and z world axes.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OrientedBox {
+    pub centre: (f32, f32),
+    pub forward: (f32, f32),
+    pub right: (f32, f32),
+    pub half_length: f32,
+    pub half_width: f32,
+}
+
+impl OrientedBox {
+    /// Returns `None` for unused car slots, which have no direction.
+    pub fn new(car: &CarMotionData, dimensions: CarDimensions) -> Option<Self> {
+        let direction = |x: i16, z: i16| -> Option<(f32, f32)> {
+            let (x, z) = (x as f32, z as f32);
+            let length = (x * x + z * z).sqrt();
+            if length == 0.0 {
+                None
+            } else {
+                Some((x / length, z / length))
+            }
+        };
+
+        Some(OrientedBox {
+            centre: (car.world_position.x, car.world_position.z),
+            forward: direction(car.world_forward_dir.x, car.world_forward_dir.z)?,
+            right: direction(car.world_right_dir.x, car.world_right_dir.z)?,
+            half_length: dimensions.length / 2.0,
+            half_width: dimensions.width / 2.0,
+        })
+    }
+
+    fn radius(&self, axis: (f32, f32)) -> f32 {
+        self.half_length * dot(self.forward, axis).abs()
+            + self.half_width * dot(self.right, axis).abs()
+    }
+
+    /// Gap between the two boxes along the axis that separates them best.
+    /// Negative when they overlap.
+    pub fn clearance(&self, other: &OrientedBox) -> f32 {
+        let offset = (
+            other.centre.0 - self.centre.0,
+            other.centre.1 - self.centre.1,
+        );
+        [self.forward, self.right, other.forward, other.right]
+            .iter()
+            .map(|&axis| dot(offset, axis).abs() - self.radius(axis) - other.radius(axis))
+            .fold(f32::MIN, f32::max)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// Closest car alongside the player on each side.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct Spotter {
+    pub left: Option<u8>,
+    pub right: Option<u8>,
+}
+
+impl Spotter {
+    pub fn alongside(&self, side: Side) -> Option<u8> {
+        match side {
+            Side::Left => self.left,
+            Side::Right => self.right,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Ord)]
+pub enum ProximityKind {
+    NearMiss,
+    Contact,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ProximityEvent {
+    pub kind: ProximityKind,
+    pub vehicles: (u8, u8),
+    pub session_time: f32,
+    pub clearance: f32,
+}
+
+/// Distance between the centres of every pair of cars, indexed by vehicle.
+pub fn distances(data: &MotionData) -> Vec<Vec<f32>> {
+    data.car_motion_data
+        .iter()
+        .map(|car| {
+            data.car_motion_data
+                .iter()
+                .map(|other| car.world_position.distance(&other.world_position))
+                .collect()
+        })
+        .collect()
+}
+
+/// Finds contacts and near misses between cars and keeps a spotter for the
+/// player.
+#[derive(Debug, Default)]
+pub struct ProximityDetector {
+    config: ProximityConfig,
+    close: Vec<((u8, u8), ProximityKind)>,
+    spotter: Spotter,
+}
+
+impl ProximityDetector {
+    pub fn new(config: ProximityConfig) -> Self {
+        ProximityDetector {
+            config,
+            ..ProximityDetector::default()
+        }
+    }
+
+    pub fn spotter(&self) -> Spotter {
+        self.spotter
+    }
+
+    /// Returns pairs of cars that came close this frame. A pair is reported
+    /// again only after separating or when a near miss turns into contact.
+    pub fn update(&mut self, packet: &Telemetry) -> Vec<ProximityEvent> {
+        let data = match packet.data {
+            TelemetryData::Motion(ref data) => data,
+            _ => return Vec::new(),
+        };
+
+        let boxes: Vec<Option<OrientedBox>> = data
+            .car_motion_data
+            .iter()
+            .map(|car| OrientedBox::new(car, self.config.dimensions))
+            .collect();
+        self.spotter = self.find_alongside(&boxes, packet.header.player_car_index as usize);
+
+        let mut close = Vec::new();
+        let mut events = Vec::new();
+        for (index, car) in boxes.iter().enumerate() {
+            let car = match car {
+                Some(car) => car,
+                None => continue,
+            };
+            for (other_index, other) in boxes.iter().enumerate().skip(index + 1) {
+                let other = match other {
+                    Some(other) => other,
+                    None => continue,
+                };
+
+                let clearance = car.clearance(other);
+                let kind = if clearance <= 0.0 {
+                    ProximityKind::Contact
+                } else if clearance <= self.config.near_miss {
+                    ProximityKind::NearMiss
+                } else {
+                    continue;
+                };
+
+                let vehicles = (index as u8, other_index as u8);
+                let previous = self
+                    .close
+                    .iter()
+                    .find(|(pair, _)| *pair == vehicles)
+                    .map(|(_, kind)| *kind);
+                let kind = previous.map_or(kind, |previous| previous.max(kind));
+                if previous != Some(kind) {
+                    events.push(ProximityEvent {
+                        kind,
+                        vehicles,
+                        session_time: packet.header.session_time,
+                        clearance,
+                    });
+                }
+                close.push((vehicles, kind));
+            }
+        }
+
+        self.close = close;
+        events
+    }
+
+    fn find_alongside(&self, boxes: &[Option<OrientedBox>], player: usize) -> Spotter {
+        let mut spotter = Spotter::default();
+        let player_box = match boxes.get(player) {
+            Some(Some(player_box)) => player_box,
+            _ => return spotter,
+        };
+
+        let mut closest = [f32::MAX; 2];
+        for (index, other) in boxes.iter().enumerate() {
+            let other = match other {
+                Some(other) if index != player => other,
+                _ => continue,
+            };
+
+            let offset = (
+                other.centre.0 - player_box.centre.0,
+                other.centre.1 - player_box.centre.1,
+            );
+            let ahead = dot(offset, player_box.forward);
+            let sideways = dot(offset, player_box.right);
+            let gap = sideways.abs() - 2.0 * player_box.half_width;
+            if ahead.abs() >= 2.0 * player_box.half_length || gap > self.config.spotter_range {
+                continue;
+            }
+
+            let (slot, vehicle) = if sideways >= 0.0 {
+                (1, &mut spotter.right)
+            } else {
+                (0, &mut spotter.left)
+            };
+            if sideways.abs() < closest[slot] {
+                closest[slot] = sideways.abs();
+                *vehicle = Some(index as u8);
+            }
+        }
+
+        spotter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{car_motion, motion_data, motion_packet};
+
+    #[test]
+    fn test_clearance() {
+        let dimensions = CarDimensions::default();
+        let car = OrientedBox::new(&car_motion(0.0, 0.0), dimensions).unwrap();
+        let behind = OrientedBox::new(&car_motion(0.0, -6.6), dimensions).unwrap();
+        assert!((car.clearance(&behind) - 1.0).abs() < 1e-4);
+
+        let mut unused = car_motion(0.0, 0.0);
+        unused.world_forward_dir.z = 0;
+        assert_eq!(OrientedBox::new(&unused, dimensions), None);
+    }
+
+    #[test]
+    fn test_near_miss_contact_and_spotter() {
+        let mut detector = ProximityDetector::default();
+        // The right direction of the test cars points along negative x.
+        let frame = |x| {
+            motion_data(vec![
+                car_motion(0.0, 0.0),
+                car_motion(x, 1.0),
+                car_motion(50.0, 0.0),
+            ])
+        };
+
+        let events = detector.update(&motion_packet(1, 0.0, 0, frame(-2.3)));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, ProximityKind::NearMiss);
+        assert_eq!(events[0].vehicles, (0, 1));
+        assert_eq!(
+            detector.spotter(),
+            Spotter {
+                left: None,
+                right: Some(1)
+            }
+        );
+
+        assert!(detector
+            .update(&motion_packet(1, 0.1, 1, frame(-2.2)))
+            .is_empty());
+        let events = detector.update(&motion_packet(1, 0.2, 2, frame(-1.8)));
+        assert_eq!(events[0].kind, ProximityKind::Contact);
+        assert!(detector
+            .update(&motion_packet(1, 0.3, 3, frame(-2.2)))
+            .is_empty());
+
+        detector.update(&motion_packet(1, 0.4, 4, frame(-10.0)));
+        assert_eq!(detector.spotter(), Spotter::default());
+        let events = detector.update(&motion_packet(1, 0.5, 5, frame(2.4)));
+        assert_eq!(events[0].kind, ProximityKind::NearMiss);
+        assert_eq!(detector.spotter().alongside(Side::Left), Some(1));
+    }
+
+    #[test]
+    fn test_distances() {
+        let data = motion_data(vec![car_motion(0.0, 0.0), car_motion(3.0, 4.0)]);
+        assert_eq!(distances(&data), vec![vec![0.0, 5.0], vec![5.0, 0.0]]);
+    }
+}
@@ -0,0 +1,365 @@
+use crate::mappings::{LapState, SessionType};
+use crate::packets::LapData;
+use crate::{Telemetry, TelemetryData};
+
+/// Number of cars knocked out at the end of Q1 and Q2.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Knockout {
+    pub q1_eliminated: usize,
+    pub q2_eliminated: usize,
+}
+
+impl Default for Knockout {
+    fn default() -> Self {
+        Knockout {
+            q1_eliminated: 5,
+            q2_eliminated: 5,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GridSlot {
+    pub position: u8,
+    pub vehicle_index: u8,
+    /// Best valid lap in the session that decided this grid slot.
+    pub best_lap_time: Option<f32>,
+    /// The last qualifying session the car took part in.
+    pub session_type: SessionType,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct GridDifference {
+    pub vehicle_index: u8,
+    pub qualified: u8,
+    /// `LapData::grid_position` reported in the race.
+    pub grid_position: u8,
+}
+
+impl GridDifference {
+    /// Places lost between qualifying and the race grid, e.g. from penalties.
+    pub fn places_dropped(&self) -> i16 {
+        self.grid_position as i16 - self.qualified as i16
+    }
+}
+
+fn is_qualifying(session_type: SessionType) -> bool {
+    matches!(
+        session_type,
+        SessionType::Qualifying1
+            | SessionType::Qualifying2
+            | SessionType::Qualifying3
+            | SessionType::ShortQualifying
+            | SessionType::OneShotQualifying
+    )
+}
+
+/// Best valid lap per car for each qualifying session. A session restarted
+/// under a new `session_uid` replaces the earlier one of the same type.
+#[derive(Debug, Default)]
+pub struct QualifyingTracker {
+    knockout: Knockout,
+    session_uid: u64,
+    session_type: Option<SessionType>,
+    previous_laps: Vec<LapData>,
+    sessions: Vec<(u64, SessionType, Vec<Option<f32>>)>,
+}
+
+impl QualifyingTracker {
+    pub fn new(knockout: Knockout) -> Self {
+        QualifyingTracker {
+            knockout,
+            ..QualifyingTracker::default()
+        }
+    }
+
+    pub fn update(&mut self, packet: &Telemetry) {
+        if packet.header.session_uid != self.session_uid {
+            self.session_uid = packet.header.session_uid;
+            self.session_type = None;
+            self.previous_laps.clear();
+        }
+
+        match packet.data {
+            TelemetryData::Session(ref data) => {
+                if self.session_type != Some(data.session_type) {
+                    self.previous_laps.clear();
+                }
+                self.session_type = Some(data.session_type);
+            }
+            TelemetryData::Lap(ref data) => self.update_laps(&data.lap_data),
+            _ => {}
+        }
+    }
+
+    fn update_laps(&mut self, laps: &[LapData]) {
+        let previous_laps = std::mem::replace(&mut self.previous_laps, laps.to_vec());
+        let session_type = match self.session_type {
+            Some(session_type) if is_qualifying(session_type) => session_type,
+            _ => return,
+        };
+
+        let session_uid = self.session_uid;
+        let index = match self
+            .sessions
+            .iter()
+            .position(|(uid, existing, _)| *uid == session_uid && *existing == session_type)
+        {
+            Some(index) => index,
+            None => {
+                self.sessions.push((session_uid, session_type, Vec::new()));
+                self.sessions.len() - 1
+            }
+        };
+        let best_laps = &mut self.sessions[index].2;
+        best_laps.resize(laps.len(), None);
+
+        for (vehicle, (previous, current)) in previous_laps.iter().zip(laps).enumerate() {
+            let completed = current.current_lap_num > previous.current_lap_num
+                && previous.current_lap_invalid == LapState::Valid
+                && current.last_lap_time > 0.0;
+            if !completed {
+                continue;
+            }
+
+            let best = &mut best_laps[vehicle];
+            if best.is_none() || Some(current.last_lap_time) < *best {
+                *best = Some(current.last_lap_time);
+            }
+        }
+    }
+
+    /// Best valid lap of every car in the latest session of a type.
+    pub fn best_laps(&self, session_type: SessionType) -> Option<&[Option<f32>]> {
+        self.sessions
+            .iter()
+            .rev()
+            .find(|(_, existing, _)| *existing == session_type)
+            .map(|(_, _, best_laps)| &best_laps[..])
+    }
+
+    /// Classifies `vehicles` by their best lap in `session_type`, with cars
+    /// that never set a valid time at the back.
+    fn order(&self, session_type: SessionType, vehicles: &[u8]) -> Vec<(u8, Option<f32>)> {
+        let best_laps = self.best_laps(session_type).unwrap_or(&[]);
+        let mut order: Vec<(u8, Option<f32>)> = vehicles
+            .iter()
+            .map(|&vehicle| (vehicle, best_laps.get(vehicle as usize).copied().flatten()))
+            .collect();
+        order.sort_by(
+            |(a_vehicle, a_time), (b_vehicle, b_time)| match (a_time, b_time) {
+                (Some(a), Some(b)) => a.total_cmp(b).then(a_vehicle.cmp(b_vehicle)),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => a_vehicle.cmp(b_vehicle),
+            },
+        );
+        order
+    }
+
+    /// Builds the starting grid, applying the knockout across Q1 to Q3 or
+    /// using the single session for short and one-shot qualifying.
+    pub fn grid(&self, vehicles: &[u8]) -> Vec<GridSlot> {
+        let single = [SessionType::OneShotQualifying, SessionType::ShortQualifying]
+            .iter()
+            .find(|session_type| self.best_laps(**session_type).is_some());
+        let stages: Vec<(SessionType, usize)> = match single {
+            Some(&session_type) => vec![(session_type, 0)],
+            None => vec![
+                (SessionType::Qualifying1, self.knockout.q1_eliminated),
+                (SessionType::Qualifying2, self.knockout.q2_eliminated),
+                (SessionType::Qualifying3, 0),
+            ],
+        };
+
+        let mut remaining = vehicles.to_vec();
+        let mut eliminated: Vec<Vec<GridSlot>> = Vec::new();
+        for (stage, (session_type, knocked_out)) in stages.iter().enumerate() {
+            let order = self.order(*session_type, &remaining);
+            let last_stage =
+                stage == stages.len() - 1 || self.best_laps(stages[stage + 1].0).is_none();
+            let cut = if last_stage {
+                0
+            } else {
+                order.len().saturating_sub(*knocked_out)
+            };
+
+            eliminated.push(
+                order[cut..]
+                    .iter()
+                    .map(|&(vehicle_index, best_lap_time)| GridSlot {
+                        position: 0,
+                        vehicle_index,
+                        best_lap_time,
+                        session_type: *session_type,
+                    })
+                    .collect(),
+            );
+            remaining = order[..cut].iter().map(|&(vehicle, _)| vehicle).collect();
+            if last_stage {
+                break;
+            }
+        }
+
+        eliminated
+            .into_iter()
+            .rev()
+            .flatten()
+            .enumerate()
+            .map(|(position, slot)| GridSlot {
+                position: position as u8 + 1,
+                ..slot
+            })
+            .collect()
+    }
+
+    /// Compares the qualifying result with the grid the race started from.
+    pub fn compare_with_race(&self, race_laps: &[LapData]) -> Vec<GridDifference> {
+        let vehicles: Vec<u8> = race_laps
+            .iter()
+            .enumerate()
+            .filter(|(_, lap)| lap.grid_position > 0)
+            .map(|(vehicle, _)| vehicle as u8)
+            .collect();
+
+        self.grid(&vehicles)
+            .into_iter()
+            .map(|slot| GridDifference {
+                vehicle_index: slot.vehicle_index,
+                qualified: slot.position,
+                grid_position: race_laps[slot.vehicle_index as usize].grid_position,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{lap_data, lap_packet, session_data, session_packet};
+    use crate::mappings::TrackId;
+
+    fn run_session(
+        tracker: &mut QualifyingTracker,
+        session_uid: u64,
+        session_type: SessionType,
+        times: &[f32],
+    ) {
+        tracker.update(&session_packet(
+            session_uid,
+            0.0,
+            0,
+            session_data(session_type, TrackId::Monza),
+        ));
+        let laps = |lap_num, invalid: bool| {
+            times
+                .iter()
+                .map(|&time| {
+                    let mut lap = lap_data(1, lap_num, 0.0);
+                    lap.last_lap_time = time;
+                    if invalid {
+                        lap.current_lap_invalid = LapState::Invalid;
+                    }
+                    lap
+                })
+                .collect::<Vec<_>>()
+        };
+        tracker.update(&lap_packet(session_uid, 1.0, 1, laps(1, false)));
+        tracker.update(&lap_packet(session_uid, 2.0, 2, laps(2, false)));
+    }
+
+    #[test]
+    fn test_invalid_laps_are_ignored() {
+        let mut tracker = QualifyingTracker::new(Knockout::default());
+        tracker.update(&session_packet(
+            1,
+            0.0,
+            0,
+            session_data(SessionType::Qualifying1, TrackId::Monza),
+        ));
+
+        let mut invalid = lap_data(1, 1, 0.0);
+        invalid.current_lap_invalid = LapState::Invalid;
+        tracker.update(&lap_packet(1, 1.0, 1, vec![invalid, lap_data(2, 1, 0.0)]));
+
+        let mut fast = lap_data(1, 2, 0.0);
+        fast.last_lap_time = 80.0;
+        let mut slow = lap_data(2, 2, 0.0);
+        slow.last_lap_time = 82.0;
+        tracker.update(&lap_packet(1, 2.0, 2, vec![fast, slow]));
+
+        assert_eq!(
+            tracker.best_laps(SessionType::Qualifying1),
+            Some(&[None, Some(82.0)][..])
+        );
+    }
+
+    #[test]
+    fn test_restarted_session_replaces_earlier_one() {
+        let mut tracker = QualifyingTracker::new(Knockout::default());
+        run_session(&mut tracker, 1, SessionType::Qualifying1, &[80.0, 81.0]);
+        run_session(&mut tracker, 2, SessionType::Qualifying1, &[82.0, 81.5]);
+
+        assert_eq!(
+            tracker.best_laps(SessionType::Qualifying1),
+            Some(&[Some(82.0), Some(81.5)][..])
+        );
+        let grid: Vec<u8> = tracker
+            .grid(&[0, 1])
+            .iter()
+            .map(|slot| slot.vehicle_index)
+            .collect();
+        assert_eq!(grid, vec![1, 0]);
+    }
+
+    #[test]
+    fn test_knockout_grid() {
+        let mut tracker = QualifyingTracker::new(Knockout {
+            q1_eliminated: 2,
+            q2_eliminated: 2,
+        });
+        run_session(
+            &mut tracker,
+            1,
+            SessionType::Qualifying1,
+            &[80.0, 81.0, 82.0, 83.0, 84.0, 85.0],
+        );
+        run_session(
+            &mut tracker,
+            2,
+            SessionType::Qualifying2,
+            &[79.0, 79.5, 80.0, 79.8, 0.0, 0.0],
+        );
+        run_session(
+            &mut tracker,
+            3,
+            SessionType::Qualifying3,
+            &[78.0, 78.5, 0.0, 0.0, 0.0, 0.0],
+        );
+
+        let grid: Vec<(u8, SessionType)> = tracker
+            .grid(&[0, 1, 2, 3, 4, 5])
+            .iter()
+            .map(|slot| (slot.vehicle_index, slot.session_type))
+            .collect();
+        assert_eq!(
+            grid,
+            vec![
+                (0, SessionType::Qualifying3),
+                (1, SessionType::Qualifying3),
+                (3, SessionType::Qualifying2),
+                (2, SessionType::Qualifying2),
+                (4, SessionType::Qualifying1),
+                (5, SessionType::Qualifying1),
+            ]
+        );
+
+        let mut race_laps: Vec<LapData> =
+            (1..=6).map(|position| lap_data(position, 1, 0.0)).collect();
+        race_laps[3].grid_position = 6;
+        race_laps[5].grid_position = 4;
+        let differences = tracker.compare_with_race(&race_laps);
+        assert_eq!(differences[2].vehicle_index, 3);
+        assert_eq!(differences[2].places_dropped(), 3);
+    }
+}
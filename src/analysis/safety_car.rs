@@ -0,0 +1,283 @@
+use crate::mappings::{PitStatus, SafetyCarStatus};
+use crate::packets::LapData;
+use crate::{Telemetry, TelemetryData};
+
+/// How much of the leader's progress is kept to measure time gaps, in
+/// seconds. Cars further behind than this have no gap.
+const LEADER_HISTORY: f32 = 300.0;
+
+/// What one car did during a safety car or virtual safety car period.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CarPeriod {
+    pub vehicle_index: u8,
+    /// The car entered the pits during the period. Cars already in the pit
+    /// lane when it started don't count.
+    pub pitted: bool,
+    /// Lowest `LapData::safety_car_delta` seen. Negative values mean the car
+    /// was faster than allowed.
+    pub minimum_delta: Option<f32>,
+    /// Time spent running faster than the delta.
+    pub time_too_fast: f32,
+    pub position_at_start: u8,
+    pub position_at_end: u8,
+    /// Time behind the leader, measured at the car's position on track.
+    pub gap_at_start: Option<f32>,
+    pub gap_at_end: Option<f32>,
+}
+
+impl CarPeriod {
+    pub fn complied(&self) -> bool {
+        self.time_too_fast == 0.0
+    }
+
+    /// Time gained on the leader over the period, negative when time was
+    /// lost.
+    pub fn time_gained(&self) -> Option<f32> {
+        Some(self.gap_at_start? - self.gap_at_end?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SafetyCarPeriod {
+    pub status: SafetyCarStatus,
+    pub start: f32,
+    pub end: Option<f32>,
+    pub cars: Vec<CarPeriod>,
+}
+
+impl SafetyCarPeriod {
+    pub fn pitted(&self) -> Vec<u8> {
+        self.cars
+            .iter()
+            .filter(|car| car.pitted)
+            .map(|car| car.vehicle_index)
+            .collect()
+    }
+
+    /// Time `vehicle` gained over `other` during the period.
+    pub fn gained_over(&self, vehicle: u8, other: u8) -> Option<f32> {
+        let gained = |vehicle: u8| self.cars.get(vehicle as usize)?.time_gained();
+        Some(gained(vehicle)? - gained(other)?)
+    }
+}
+
+/// Detects safety car periods and measures how each car used them.
+#[derive(Debug, Default)]
+pub struct SafetyCarAnalyzer {
+    status: Option<SafetyCarStatus>,
+    last_time: Option<f32>,
+    laps: Vec<LapData>,
+    /// Session time and total distance of the leader.
+    leader: Vec<(f32, f32)>,
+    periods: Vec<SafetyCarPeriod>,
+}
+
+impl SafetyCarAnalyzer {
+    pub fn new() -> Self {
+        SafetyCarAnalyzer::default()
+    }
+
+    pub fn periods(&self) -> &[SafetyCarPeriod] {
+        &self.periods
+    }
+
+    /// Returns the period that ended with this packet, if any.
+    pub fn update(&mut self, packet: &Telemetry) -> Option<&SafetyCarPeriod> {
+        let time = packet.header.session_time;
+        match packet.data {
+            TelemetryData::Session(ref data) => {
+                let previous = self.status.replace(data.safety_car_status);
+                if previous.is_none() || previous == Some(data.safety_car_status) {
+                    return None;
+                }
+
+                let ended = self.close_period(time);
+                if data.safety_car_status != SafetyCarStatus::None {
+                    self.open_period(time, data.safety_car_status);
+                }
+                if ended {
+                    return self
+                        .periods
+                        .iter()
+                        .rev()
+                        .find(|period| period.end.is_some());
+                }
+            }
+            TelemetryData::Lap(ref data) => self.update_laps(time, &data.lap_data),
+            _ => {}
+        }
+        None
+    }
+
+    fn open_period(&mut self, time: f32, status: SafetyCarStatus) {
+        let laps_time = self.last_time.unwrap_or(time);
+        let cars = self
+            .laps
+            .iter()
+            .enumerate()
+            .map(|(vehicle, lap)| CarPeriod {
+                vehicle_index: vehicle as u8,
+                pitted: false,
+                minimum_delta: None,
+                time_too_fast: 0.0,
+                position_at_start: lap.car_position,
+                position_at_end: lap.car_position,
+                gap_at_start: self.gap(laps_time, lap),
+                gap_at_end: None,
+            })
+            .collect();
+
+        self.periods.push(SafetyCarPeriod {
+            status,
+            start: time,
+            end: None,
+            cars,
+        });
+    }
+
+    /// Returns true if a period was open.
+    fn close_period(&mut self, time: f32) -> bool {
+        let laps_time = self.last_time.unwrap_or(time);
+        let gaps: Vec<Option<f32>> = self
+            .laps
+            .iter()
+            .map(|lap| self.gap(laps_time, lap))
+            .collect();
+        let period = match self.periods.last_mut() {
+            Some(period) if period.end.is_none() => period,
+            _ => return false,
+        };
+
+        period.end = Some(time);
+        for (car, gap) in period.cars.iter_mut().zip(gaps) {
+            car.gap_at_end = gap;
+        }
+        true
+    }
+
+    fn update_laps(&mut self, time: f32, laps: &[LapData]) {
+        let dt = match self.last_time.replace(time) {
+            Some(last) if time > last => time - last,
+            _ => 0.0,
+        };
+        let previous = std::mem::replace(&mut self.laps, laps.to_vec());
+
+        if let Some(leader) = laps.iter().find(|lap| lap.car_position == 1) {
+            self.leader.push((time, leader.total_distance));
+            let oldest = self
+                .leader
+                .iter()
+                .position(|(sample, _)| time - sample <= LEADER_HISTORY)
+                .unwrap_or(0);
+            self.leader.drain(..oldest);
+        }
+
+        let period = match self.periods.last_mut() {
+            Some(period) if period.end.is_none() => period,
+            _ => return,
+        };
+        for (car, (lap, previous)) in period.cars.iter_mut().zip(laps.iter().zip(previous)) {
+            car.position_at_end = lap.car_position;
+            car.pitted |=
+                previous.pit_status == PitStatus::None && lap.pit_status != PitStatus::None;
+            let delta = lap.safety_car_delta;
+            if car.minimum_delta.is_none() || Some(delta) < car.minimum_delta {
+                car.minimum_delta = Some(delta);
+            }
+            if delta < 0.0 {
+                car.time_too_fast += dt;
+            }
+        }
+    }
+
+    /// Time between the leader and the car passing the point `lap` is at,
+    /// with `time` being when `lap` was received.
+    fn gap(&self, time: f32, lap: &LapData) -> Option<f32> {
+        let distance = lap.total_distance;
+        let after = self
+            .leader
+            .iter()
+            .position(|(_, leader_distance)| *leader_distance >= distance)?;
+        let (after_time, after_distance) = self.leader[after];
+        if after_distance == distance {
+            return Some(time - after_time);
+        }
+
+        let (before_time, before_distance) = *self.leader.get(after.checked_sub(1)?)?;
+        let fraction = (distance - before_distance) / (after_distance - before_distance);
+        Some(time - (before_time + fraction * (after_time - before_time)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{lap_data, lap_packet, session_data, session_packet};
+    use crate::mappings::{SessionType, TrackId};
+
+    fn session(time: f32, status: SafetyCarStatus) -> Telemetry<'static> {
+        let mut data = session_data(SessionType::Race, TrackId::Monza);
+        data.safety_car_status = status;
+        session_packet(1, time, 0, data)
+    }
+
+    #[test]
+    fn test_virtual_safety_car_period() {
+        let mut analyzer = SafetyCarAnalyzer::new();
+        assert!(analyzer
+            .update(&session(0.0, SafetyCarStatus::None))
+            .is_none());
+
+        // The leader runs at 50 m/s throughout. The second car is 5 seconds
+        // behind and closes up at 60 m/s during the period.
+        for tick in 0..=20 {
+            let time = tick as f32;
+            if tick == 10 {
+                analyzer.update(&session(time, SafetyCarStatus::Virtual));
+            }
+
+            let mut chaser = lap_data(2, 1, 750.0 + 50.0 * time + 10.0 * (time - 10.0).max(0.0));
+            if tick > 10 {
+                chaser.safety_car_delta = if tick <= 12 { -0.5 } else { 1.0 };
+            }
+            let mut pitting = lap_data(3, 1, 100.0 + time);
+            if tick == 15 {
+                pitting.pit_status = PitStatus::Pitting;
+            }
+            // Already in the pit lane when the period starts.
+            let mut in_pits = lap_data(4, 1, 50.0 + time);
+            if (8..=12).contains(&tick) {
+                in_pits.pit_status = PitStatus::InPitArea;
+            }
+            analyzer.update(&lap_packet(
+                1,
+                time,
+                tick,
+                vec![
+                    lap_data(1, 1, 1000.0 + 50.0 * time),
+                    chaser,
+                    pitting,
+                    in_pits,
+                ],
+            ));
+        }
+
+        let period = analyzer
+            .update(&session(20.0, SafetyCarStatus::None))
+            .cloned()
+            .unwrap();
+        assert_eq!(period.status, SafetyCarStatus::Virtual);
+        assert_eq!((period.start, period.end), (10.0, Some(20.0)));
+        assert_eq!(period.pitted(), vec![2]);
+
+        let chaser = &period.cars[1];
+        assert!(!chaser.complied());
+        assert_eq!(chaser.time_too_fast, 2.0);
+        assert_eq!(chaser.minimum_delta, Some(-0.5));
+        assert_eq!(chaser.gap_at_start, Some(5.0));
+        assert_eq!(chaser.gap_at_end, Some(3.0));
+        assert_eq!(chaser.time_gained(), Some(2.0));
+        assert_eq!(period.gained_over(1, 0), Some(2.0));
+        assert_eq!(period.cars[2].gap_at_start, None);
+    }
+}
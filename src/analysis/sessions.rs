@@ -0,0 +1,286 @@
+use crate::analysis::flashback::FlashbackDetector;
+use crate::mappings::{SessionType, TrackId};
+use crate::packets::EventDataDetails;
+use crate::{Telemetry, TelemetryData};
+
+/// A rewind that lands within this many seconds of the session clock starting
+/// is a restart rather than a flashback.
+const RESTART_THRESHOLD: f32 = 1.0;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BoundaryReason {
+    /// The first packet seen by the demultiplexer.
+    FirstPacket,
+    /// `Header::session_uid` changed, e.g. moving on to the next session.
+    NewSessionUid,
+    /// The session clock went back to the start of the session.
+    Restart,
+    /// An `SSTA` event arrived for a session that was already under way.
+    SessionStarted,
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionInfo {
+    pub session_uid: u64,
+    pub session_type: Option<SessionType>,
+    pub track_id: Option<TrackId>,
+    pub reason: BoundaryReason,
+    pub start_time: f32,
+    pub last_time: f32,
+    pub ended: bool,
+    pub rewinds: u32,
+    pub packets: usize,
+}
+
+impl SessionInfo {
+    fn new(session_uid: u64, session_time: f32, reason: BoundaryReason) -> Self {
+        SessionInfo {
+            session_uid,
+            session_type: None,
+            track_id: None,
+            reason,
+            start_time: session_time,
+            last_time: session_time,
+            ended: false,
+            rewinds: 0,
+            packets: 0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Routing {
+    /// Index of the session the packet belongs to.
+    pub session: usize,
+    /// Set when the packet opened a new session.
+    pub boundary: Option<BoundaryReason>,
+    /// Set when the packet jumped backwards within the current session.
+    pub rewound: bool,
+}
+
+/// Splits a packet stream into separate sessions. Rewinds are found by a
+/// `FlashbackDetector`, so both agree on what counts as one.
+#[derive(Debug, Default)]
+pub struct SessionDemultiplexer {
+    detector: FlashbackDetector,
+    sessions: Vec<SessionInfo>,
+}
+
+impl SessionDemultiplexer {
+    pub fn new() -> Self {
+        SessionDemultiplexer::default()
+    }
+
+    pub fn sessions(&self) -> &[SessionInfo] {
+        &self.sessions
+    }
+
+    pub fn current(&self) -> Option<&SessionInfo> {
+        self.sessions.last()
+    }
+
+    pub fn push(&mut self, packet: &Telemetry) -> Routing {
+        let uid = packet.header.session_uid;
+        let time = packet.header.session_time;
+        let rewind = self.detector.push(packet);
+        let mut rewound = false;
+
+        let boundary = match self.sessions.last() {
+            None => Some(BoundaryReason::FirstPacket),
+            Some(current) if current.session_uid != uid => Some(BoundaryReason::NewSessionUid),
+            Some(current) => match packet.data {
+                TelemetryData::Event(ref event)
+                    if matches!(event.event_details, EventDataDetails::SessionStarted)
+                        && (current.ended
+                            || current.last_time - current.start_time > RESTART_THRESHOLD) =>
+                {
+                    Some(BoundaryReason::SessionStarted)
+                }
+                // Any packet can be the first one after a rewind, events included.
+                _ => match rewind {
+                    Some(rewind) if rewind.to_time < RESTART_THRESHOLD => {
+                        Some(BoundaryReason::Restart)
+                    }
+                    Some(_) => {
+                        rewound = true;
+                        None
+                    }
+                    None => None,
+                },
+            },
+        };
+
+        if let Some(reason) = boundary {
+            let mut session = SessionInfo::new(uid, time, reason);
+            // A restart keeps the same session, so carry the tags across until
+            // the next session packet confirms them.
+            if let Some(previous) = self.sessions.last() {
+                if previous.session_uid == uid {
+                    session.session_type = previous.session_type;
+                    session.track_id = previous.track_id;
+                }
+            }
+            self.sessions.push(session);
+        }
+
+        let session = self.sessions.last_mut().unwrap();
+        session.packets += 1;
+        if rewound {
+            session.rewinds += 1;
+            session.last_time = time;
+        } else if time > session.last_time {
+            session.last_time = time;
+        }
+
+        match packet.data {
+            TelemetryData::Session(ref data) => {
+                session.session_type = Some(data.session_type);
+                session.track_id = Some(data.track_id);
+            }
+            TelemetryData::Event(ref event) => {
+                if let EventDataDetails::SessionEnded = event.event_details {
+                    session.ended = true;
+                }
+            }
+            _ => {}
+        }
+
+        Routing {
+            session: self.sessions.len() - 1,
+            boundary,
+            rewound,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Session<'a> {
+    pub info: SessionInfo,
+    pub packets: Vec<Telemetry<'a>>,
+}
+
+/// Splits a recorded packet stream into one packet list per session.
+pub fn split_sessions<'a, I>(packets: I) -> Vec<Session<'a>>
+where
+    I: IntoIterator<Item = Telemetry<'a>>,
+{
+    let mut demultiplexer = SessionDemultiplexer::new();
+    let mut streams: Vec<Vec<Telemetry<'a>>> = Vec::new();
+
+    for packet in packets {
+        let routing = demultiplexer.push(&packet);
+        if routing.session == streams.len() {
+            streams.push(Vec::new());
+        }
+        streams[routing.session].push(packet);
+    }
+
+    demultiplexer
+        .sessions
+        .into_iter()
+        .zip(streams)
+        .map(|(info, packets)| Session { info, packets })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{event_packet, session_data, session_packet};
+
+    #[test]
+    fn test_split_sessions() {
+        let packets = vec![
+            session_packet(
+                1,
+                0.0,
+                0,
+                session_data(SessionType::Practice1, TrackId::Monza),
+            ),
+            event_packet(1, 0.1, 1, "SSTA", EventDataDetails::SessionStarted),
+            session_packet(
+                1,
+                60.0,
+                100,
+                session_data(SessionType::Practice1, TrackId::Monza),
+            ),
+            session_packet(
+                1,
+                50.0,
+                90,
+                session_data(SessionType::Practice1, TrackId::Monza),
+            ),
+            event_packet(1, 0.0, 0, "SSTA", EventDataDetails::SessionStarted),
+            session_packet(
+                1,
+                0.2,
+                1,
+                session_data(SessionType::Practice1, TrackId::Monza),
+            ),
+            event_packet(1, 30.0, 50, "SEND", EventDataDetails::SessionEnded),
+            session_packet(2, 0.0, 0, session_data(SessionType::Race, TrackId::Monza)),
+        ];
+
+        let sessions = split_sessions(packets);
+        assert_eq!(sessions.len(), 3);
+
+        assert_eq!(sessions[0].info.reason, BoundaryReason::FirstPacket);
+        assert_eq!(sessions[0].info.rewinds, 1);
+        assert_eq!(sessions[0].packets.len(), 4);
+
+        assert_eq!(sessions[1].info.reason, BoundaryReason::SessionStarted);
+        assert_eq!(sessions[1].info.session_type, Some(SessionType::Practice1));
+        assert!(sessions[1].info.ended);
+        assert_eq!(sessions[1].packets.len(), 3);
+
+        assert_eq!(sessions[2].info.reason, BoundaryReason::NewSessionUid);
+        assert_eq!(sessions[2].info.session_type, Some(SessionType::Race));
+        assert_eq!(sessions[2].info.track_id, Some(TrackId::Monza));
+    }
+
+    #[test]
+    fn test_restart_without_event() {
+        let mut demultiplexer = SessionDemultiplexer::new();
+        let data = || session_data(SessionType::Race, TrackId::Spa);
+
+        demultiplexer.push(&session_packet(1, 0.0, 0, data()));
+        demultiplexer.push(&session_packet(1, 120.0, 200, data()));
+        let routing = demultiplexer.push(&session_packet(1, 0.3, 1, data()));
+
+        assert_eq!(routing.session, 1);
+        assert_eq!(routing.boundary, Some(BoundaryReason::Restart));
+        assert_eq!(demultiplexer.sessions().len(), 2);
+    }
+
+    #[test]
+    fn test_rewind_seen_on_event() {
+        let mut demultiplexer = SessionDemultiplexer::new();
+        let data = || session_data(SessionType::Race, TrackId::Spa);
+
+        demultiplexer.push(&session_packet(1, 0.0, 0, data()));
+        demultiplexer.push(&session_packet(1, 90.0, 150, data()));
+        let routing = demultiplexer.push(&event_packet(
+            1,
+            60.0,
+            100,
+            "FTLP",
+            EventDataDetails::FastestLap {
+                vehicle_index: 0,
+                lap_time: 60.0,
+            },
+        ));
+        assert!(routing.rewound);
+        demultiplexer.push(&session_packet(1, 60.1, 101, data()));
+        assert_eq!(demultiplexer.current().unwrap().rewinds, 1);
+        assert_eq!(demultiplexer.current().unwrap().last_time, 60.1);
+
+        let routing = demultiplexer.push(&event_packet(
+            1,
+            0.2,
+            1,
+            "RTMT",
+            EventDataDetails::Retirement { vehicle_index: 3 },
+        ));
+        assert_eq!(routing.boundary, Some(BoundaryReason::Restart));
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+
+use crate::error::LoadError;
+use crate::mappings::{TrackId, Weather};
+use crate::packets::CarSetupData;
+use crate::{Telemetry, TelemetryData};
+
+/// A single adjustable value of a car setup.
+#[derive(Copy, Clone)]
+pub struct SetupField {
+    pub name: &'static str,
+    pub min: f32,
+    pub max: f32,
+    get: fn(&CarSetupData) -> f32,
+    set: fn(&mut CarSetupData, f32),
+}
+
+impl SetupField {
+    pub fn get(&self, setup: &CarSetupData) -> f32 {
+        (self.get)(setup)
+    }
+
+    /// Checks `value` against the range the game allows for the field.
+    pub fn check(&self, value: f32) -> Result<(), SetupViolation> {
+        if value >= self.min - EPSILON && value <= self.max + EPSILON {
+            Ok(())
+        } else {
+            Err(SetupViolation {
+                field: self.name,
+                value,
+                min: self.min,
+                max: self.max,
+            })
+        }
+    }
+
+    /// Sets the field, refusing values outside its range rather than
+    /// squeezing them into the field's type.
+    pub fn set(&self, setup: &mut CarSetupData, value: f32) -> Result<(), SetupViolation> {
+        self.check(value)?;
+        (self.set)(setup, value);
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for SetupField {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SetupField")
+            .field("name", &self.name)
+            .field("min", &self.min)
+            .field("max", &self.max)
+            .finish()
+    }
+}
+
+macro_rules! setup_field {
+    ($name:ident, $min:expr, $max:expr, u8) => {
+        SetupField {
+            name: stringify!($name),
+            min: $min,
+            max: $max,
+            get: |setup| setup.$name as f32,
+            set: |setup, value| setup.$name = value.round() as u8,
+        }
+    };
+    ($name:ident, $min:expr, $max:expr, f32) => {
+        SetupField {
+            name: stringify!($name),
+            min: $min,
+            max: $max,
+            get: |setup| setup.$name,
+            set: |setup, value| setup.$name = value,
+        }
+    };
+}
+
+/// Every setup field with the range the game allows for it.
+pub const SETUP_FIELDS: [SetupField; 20] = [
+    setup_field!(front_wing, 1.0, 11.0, u8),
+    setup_field!(rear_wing, 1.0, 11.0, u8),
+    setup_field!(on_throttle, 50.0, 100.0, u8),
+    setup_field!(off_throttle, 50.0, 100.0, u8),
+    setup_field!(front_camber, -3.5, -2.5, f32),
+    setup_field!(rear_camber, -2.0, -1.0, f32),
+    setup_field!(front_toe, 0.05, 0.15, f32),
+    setup_field!(rear_toe, 0.2, 0.5, f32),
+    setup_field!(front_suspension, 1.0, 11.0, u8),
+    setup_field!(rear_suspension, 1.0, 11.0, u8),
+    setup_field!(front_anti_roll_bar, 1.0, 11.0, u8),
+    setup_field!(rear_anti_roll_bar, 1.0, 11.0, u8),
+    setup_field!(front_suspension_height, 1.0, 11.0, u8),
+    setup_field!(rear_suspension_height, 1.0, 11.0, u8),
+    setup_field!(brake_pressure, 50.0, 100.0, u8),
+    setup_field!(brake_bias, 50.0, 70.0, u8),
+    setup_field!(front_tyre_pressure, 21.0, 25.0, f32),
+    setup_field!(rear_tyre_pressure, 19.5, 23.5, f32),
+    setup_field!(ballast, 1.0, 11.0, u8),
+    setup_field!(fuel_load, 0.0, 110.0, f32),
+];
+
+/// Tolerance for comparing float setup values that went through the game.
+const EPSILON: f32 = 1e-4;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SetupDifference {
+    pub field: &'static str,
+    pub from: f32,
+    pub to: f32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SetupViolation {
+    pub field: &'static str,
+    pub value: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+/// Lists every field that differs between two setups.
+pub fn diff_setups(from: &CarSetupData, to: &CarSetupData) -> Vec<SetupDifference> {
+    SETUP_FIELDS
+        .iter()
+        .filter_map(|field| {
+            let (old, new) = (field.get(from), field.get(to));
+            if (old - new).abs() > EPSILON {
+                Some(SetupDifference {
+                    field: field.name,
+                    from: old,
+                    to: new,
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Lists every field outside the range the game allows.
+pub fn validate_setup(setup: &CarSetupData) -> Vec<SetupViolation> {
+    SETUP_FIELDS
+        .iter()
+        .filter_map(|field| field.check(field.get(setup)).err())
+        .collect()
+}
+
+fn track_from_name(name: &str) -> Option<TrackId> {
+    (i8::MIN..=i8::MAX)
+        .filter_map(|id| TrackId::try_from(id).ok())
+        .find(|track_id| format!("{:?}", track_id) == name)
+}
+
+fn weather_from_name(name: &str) -> Option<Weather> {
+    (u8::MIN..=u8::MAX)
+        .filter_map(|id| Weather::try_from(id).ok())
+        .find(|weather| format!("{:?}", weather) == name)
+}
+
+/// A `[setup]` section being read: its first line, track, weather and values.
+type Section = (usize, Option<TrackId>, Option<Weather>, CarSetupData);
+
+/// Setups stored per track and weather condition.
+#[derive(Debug, Default)]
+pub struct SetupLibrary {
+    setups: HashMap<(TrackId, Weather), CarSetupData>,
+}
+
+impl SetupLibrary {
+    pub fn new() -> Self {
+        SetupLibrary::default()
+    }
+
+    pub fn get(&self, track_id: TrackId, weather: Weather) -> Option<&CarSetupData> {
+        self.setups.get(&(track_id, weather))
+    }
+
+    pub fn insert(
+        &mut self,
+        track_id: TrackId,
+        weather: Weather,
+        setup: CarSetupData,
+    ) -> Option<CarSetupData> {
+        self.setups.insert((track_id, weather), setup)
+    }
+
+    pub fn remove(&mut self, track_id: TrackId, weather: Weather) -> Option<CarSetupData> {
+        self.setups.remove(&(track_id, weather))
+    }
+
+    pub fn len(&self) -> usize {
+        self.setups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.setups.is_empty()
+    }
+
+    /// Renders the library in an INI style format with one `[setup]` section
+    /// per track and weather.
+    pub fn to_text(&self) -> String {
+        let mut keys: Vec<&(TrackId, Weather)> = self.setups.keys().collect();
+        keys.sort_by_key(|(track_id, weather)| (*track_id as i8, *weather as u8));
+
+        let mut output = String::new();
+        for (index, key) in keys.into_iter().enumerate() {
+            if index > 0 {
+                output.push('\n');
+            }
+            writeln!(output, "[setup]").unwrap();
+            writeln!(output, "track = {:?}", key.0).unwrap();
+            writeln!(output, "weather = {:?}", key.1).unwrap();
+            for field in SETUP_FIELDS.iter() {
+                writeln!(output, "{} = {}", field.name, field.get(&self.setups[key])).unwrap();
+            }
+        }
+        output
+    }
+
+    fn finish(&mut self, section: Option<Section>) -> Result<(), LoadError> {
+        if let Some((line, track_id, weather, setup)) = section {
+            match (track_id, weather) {
+                (Some(track_id), Some(weather)) => {
+                    self.insert(track_id, weather, setup);
+                }
+                _ => return Err(LoadError::malformed(line)),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writer.write_all(self.to_text().as_bytes())
+    }
+
+    /// Reads a library written by `save`. Lines starting with `#` are
+    /// comments and fields missing from a section keep the value of the
+    /// `base` setup.
+    pub fn load<R: BufRead>(reader: R, base: CarSetupData) -> Result<Self, LoadError> {
+        let mut library = SetupLibrary::new();
+        let mut section: Option<Section> = None;
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line == "[setup]" {
+                library.finish(section.take())?;
+                section = Some((number, None, None, base));
+                continue;
+            }
+
+            let (_, track_id, weather, setup) = section
+                .as_mut()
+                .ok_or_else(|| LoadError::malformed(number))?;
+            let mut parts = line.splitn(2, '=').map(str::trim);
+            let (key, value) = match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => (key, value),
+                _ => return Err(LoadError::malformed(number)),
+            };
+
+            match key {
+                "track" => {
+                    *track_id =
+                        Some(track_from_name(value).ok_or_else(|| LoadError::malformed(number))?)
+                }
+                "weather" => {
+                    *weather =
+                        Some(weather_from_name(value).ok_or_else(|| LoadError::malformed(number))?)
+                }
+                _ => {
+                    let field = SETUP_FIELDS
+                        .iter()
+                        .find(|field| field.name == key)
+                        .ok_or_else(|| LoadError::malformed(number))?;
+                    let value = value.parse().map_err(|_| LoadError::malformed(number))?;
+                    field
+                        .set(setup, value)
+                        .map_err(|_| LoadError::malformed(number))?;
+                }
+            }
+        }
+        library.finish(section)?;
+
+        Ok(library)
+    }
+}
+
+/// Notices when the player changes their setup during a session.
+#[derive(Debug, Default)]
+pub struct SetupChangeDetector {
+    current: Option<CarSetupData>,
+}
+
+impl SetupChangeDetector {
+    pub fn new() -> Self {
+        SetupChangeDetector::default()
+    }
+
+    pub fn current(&self) -> Option<&CarSetupData> {
+        self.current.as_ref()
+    }
+
+    /// Returns the changed fields when the player's setup differs from the
+    /// previous setup packet.
+    pub fn update(&mut self, packet: &Telemetry) -> Option<Vec<SetupDifference>> {
+        let setup = match packet.data {
+            TelemetryData::CarSetups(ref data) => *data
+                .car_setups
+                .get(packet.header.player_car_index as usize)?,
+            _ => return None,
+        };
+
+        let differences = self
+            .current
+            .replace(setup)
+            .map(|previous| diff_setups(&previous, &setup))?;
+        if differences.is_empty() {
+            None
+        } else {
+            Some(differences)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{car_setup, car_setups_packet};
+
+    #[test]
+    fn test_diff_and_validate() {
+        let base = car_setup();
+        let mut changed = base;
+        changed.front_wing = 7;
+        changed.rear_tyre_pressure = 19.0;
+
+        assert_eq!(
+            diff_setups(&base, &changed),
+            vec![
+                SetupDifference {
+                    field: "front_wing",
+                    from: 5.0,
+                    to: 7.0,
+                },
+                SetupDifference {
+                    field: "rear_tyre_pressure",
+                    from: 20.5,
+                    to: 19.0,
+                },
+            ]
+        );
+        assert!(validate_setup(&base).is_empty());
+        assert_eq!(
+            validate_setup(&changed),
+            vec![SetupViolation {
+                field: "rear_tyre_pressure",
+                value: 19.0,
+                min: 19.5,
+                max: 23.5,
+            }]
+        );
+
+        let mut setup = base;
+        let ballast = SETUP_FIELDS
+            .iter()
+            .find(|field| field.name == "ballast")
+            .unwrap();
+        assert!(ballast.set(&mut setup, 300.0).is_err());
+        assert_eq!(setup.ballast, base.ballast);
+        assert!(ballast.set(&mut setup, 4.0).is_ok());
+        assert_eq!(setup.ballast, 4);
+    }
+
+    #[test]
+    fn test_library_round_trip() {
+        let mut library = SetupLibrary::new();
+        let mut wet = car_setup();
+        wet.front_wing = 9;
+        library.insert(TrackId::Spa, Weather::Clear, car_setup());
+        library.insert(TrackId::Spa, Weather::HeavyRain, wet);
+
+        let text = library.to_text();
+        assert!(text.starts_with("[setup]\ntrack = Spa\nweather = Clear\nfront_wing = 5\n"));
+
+        let loaded = SetupLibrary::load(text.as_bytes(), car_setup()).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.get(TrackId::Spa, Weather::HeavyRain), Some(&wet));
+
+        let edited = "# hand tuned\n[setup]\ntrack = Monza\nweather = Clear\nrear_wing = 1\n";
+        let loaded = SetupLibrary::load(edited.as_bytes(), car_setup()).unwrap();
+        assert_eq!(
+            loaded
+                .get(TrackId::Monza, Weather::Clear)
+                .unwrap()
+                .rear_wing,
+            1
+        );
+
+        let error = SetupLibrary::load("[setup]\ntrack = Nowhere\n".as_bytes(), car_setup());
+        assert_eq!(error.unwrap_err().to_string(), "Malformed data on line 2");
+        let error = SetupLibrary::load("\n[setup]\ntrack = Spa\n".as_bytes(), car_setup());
+        assert_eq!(error.unwrap_err().to_string(), "Malformed data on line 2");
+        let out_of_range = "[setup]\ntrack = Spa\nweather = Clear\nfront_wing = -3\n";
+        let error = SetupLibrary::load(out_of_range.as_bytes(), car_setup());
+        assert_eq!(error.unwrap_err().to_string(), "Malformed data on line 4");
+    }
+
+    #[test]
+    fn test_detect_setup_change() {
+        let packet = |setup| car_setups_packet(1, vec![setup]);
+
+        let mut detector = SetupChangeDetector::new();
+        assert_eq!(detector.update(&packet(car_setup())), None);
+        assert_eq!(detector.update(&packet(car_setup())), None);
+
+        let mut changed = car_setup();
+        changed.brake_bias = 58;
+        let differences = detector.update(&packet(changed)).unwrap();
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].field, "brake_bias");
+    }
+}
@@ -0,0 +1,116 @@
+/// Running minimum, maximum and mean of a series of values.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub min: f32,
+    pub max: f32,
+    pub sum: f32,
+    pub count: u32,
+}
+
+impl Summary {
+    pub fn add(&mut self, value: f32) {
+        if self.count == 0 || value < self.min {
+            self.min = value;
+        }
+        if self.count == 0 || value > self.max {
+            self.max = value;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    pub fn mean(&self) -> Option<f32> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.sum / self.count as f32)
+        }
+    }
+}
+
+/// Pearson correlation coefficient of a set of points.
+///
+/// Returns `None` with fewer than two points or when either coordinate never
+/// changes.
+pub fn pearson(points: &[(f32, f32)]) -> Option<f32> {
+    if points.len() < 2 {
+        return None;
+    }
+
+    let count = points.len() as f32;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f32>() / count;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f32>() / count;
+    let (mut covariance, mut variance_x, mut variance_y) = (0.0, 0.0, 0.0);
+    for (x, y) in points {
+        covariance += (x - mean_x) * (y - mean_y);
+        variance_x += (x - mean_x).powi(2);
+        variance_y += (y - mean_y).powi(2);
+    }
+
+    if variance_x == 0.0 || variance_y == 0.0 {
+        None
+    } else {
+        Some(covariance / (variance_x * variance_y).sqrt())
+    }
+}
+
+/// Least squares line through a set of points, as `(intercept, slope)`.
+///
+/// Returns `None` with fewer than two points or when `x` never changes.
+pub fn linear_fit(points: &[(f32, f32)]) -> Option<(f32, f32)> {
+    if points.len() < 2 {
+        return None;
+    }
+
+    let count = points.len() as f32;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f32>() / count;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f32>() / count;
+    let (mut covariance, mut variance_x) = (0.0, 0.0);
+    for (x, y) in points {
+        covariance += (x - mean_x) * (y - mean_y);
+        variance_x += (x - mean_x).powi(2);
+    }
+
+    if variance_x == 0.0 {
+        None
+    } else {
+        let slope = covariance / variance_x;
+        Some((mean_y - slope * mean_x, slope))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let mut summary = Summary::default();
+        assert_eq!(summary.mean(), None);
+
+        for value in [3.0, -1.0, 4.0].iter() {
+            summary.add(*value);
+        }
+        assert_eq!(summary.min, -1.0);
+        assert_eq!(summary.max, 4.0);
+        assert_eq!(summary.count, 3);
+        assert_eq!(summary.mean(), Some(2.0));
+    }
+
+    #[test]
+    fn test_pearson() {
+        assert_eq!(pearson(&[(1.0, 2.0), (2.0, 4.0), (3.0, 6.0)]), Some(1.0));
+        assert_eq!(pearson(&[(1.0, 3.0), (2.0, 2.0), (3.0, 1.0)]), Some(-1.0));
+        assert_eq!(pearson(&[(1.0, 1.0), (1.0, 2.0)]), None);
+        assert_eq!(pearson(&[(1.0, 1.0)]), None);
+    }
+
+    #[test]
+    fn test_linear_fit() {
+        assert_eq!(
+            linear_fit(&[(0.0, 1.0), (1.0, 3.0), (2.0, 5.0)]),
+            Some((1.0, 2.0))
+        );
+        assert_eq!(linear_fit(&[(1.0, 1.0), (1.0, 2.0)]), None);
+    }
+}
@@ -0,0 +1,285 @@
+use std::fmt::Write;
+
+use crate::analysis::event_log::{format_time, Driver};
+use crate::mappings::{LapState, SurfaceType};
+use crate::packets::{CarMotionData, CarTelemetryData, Coordinates, LapData};
+use crate::{Telemetry, TelemetryData, WheelData};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum InfringementKind {
+    Penalty { added: u8, total: u8 },
+    LapInvalidated,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Infringement {
+    pub vehicle_index: u8,
+    pub kind: InfringementKind,
+    pub lap: u8,
+    pub lap_distance: f32,
+    pub session_time: f32,
+    /// World position of the car from the latest motion packet.
+    pub world_position: Option<Coordinates<f32>>,
+    /// Surface under each wheel from the latest telemetry packet.
+    pub surface_type: Option<WheelData<SurfaceType>>,
+}
+
+impl Infringement {
+    /// Number of wheels that were off the racing surface, if known.
+    pub fn wheels_off_track(&self) -> Option<usize> {
+        self.surface_type.map(|surfaces| {
+            surfaces
+                .to_array()
+                .iter()
+                .filter(|surface| is_off_track(**surface))
+                .count()
+        })
+    }
+}
+
+fn is_off_track(surface: SurfaceType) -> bool {
+    match surface {
+        SurfaceType::Tarmac
+        | SurfaceType::RumbleStrip
+        | SurfaceType::Concrete
+        | SurfaceType::Cobblestone
+        | SurfaceType::Metal
+        | SurfaceType::Ridged => false,
+        SurfaceType::Rock
+        | SurfaceType::Gravel
+        | SurfaceType::Mud
+        | SurfaceType::Sand
+        | SurfaceType::Grass
+        | SurfaceType::Water => true,
+    }
+}
+
+/// Records penalties and invalidated laps alongside where the car was when
+/// they happened.
+#[derive(Debug, Default)]
+pub struct StewardingTracker {
+    drivers: Vec<Driver>,
+    previous_laps: Vec<LapData>,
+    motion: Vec<CarMotionData>,
+    telemetry: Vec<CarTelemetryData>,
+    infringements: Vec<Infringement>,
+}
+
+impl StewardingTracker {
+    pub fn new() -> Self {
+        StewardingTracker::default()
+    }
+
+    pub fn infringements(&self) -> &[Infringement] {
+        &self.infringements
+    }
+
+    pub fn update(&mut self, packet: &Telemetry) -> &[Infringement] {
+        let count = self.infringements.len();
+
+        match packet.data {
+            TelemetryData::Participants(ref data) => {
+                self.drivers = data.participants.iter().map(Driver::from).collect();
+            }
+            TelemetryData::Motion(ref data) => self.motion = data.car_motion_data.clone(),
+            TelemetryData::CarTelemetry(ref data) => {
+                self.telemetry = data.car_telemetry_data.clone()
+            }
+            TelemetryData::Lap(ref data) => {
+                self.update_laps(packet.header.session_time, &data.lap_data)
+            }
+            _ => {}
+        }
+
+        &self.infringements[count..]
+    }
+
+    fn update_laps(&mut self, session_time: f32, laps: &[LapData]) {
+        let previous_laps = std::mem::replace(&mut self.previous_laps, laps.to_vec());
+
+        for (index, (previous, current)) in previous_laps.iter().zip(laps).enumerate() {
+            let mut kinds = Vec::new();
+            if current.penalties > previous.penalties {
+                kinds.push((
+                    InfringementKind::Penalty {
+                        added: current.penalties - previous.penalties,
+                        total: current.penalties,
+                    },
+                    current,
+                ));
+            }
+            if previous.current_lap_invalid == LapState::Valid
+                && current.current_lap_invalid == LapState::Invalid
+            {
+                // Flagged on the packet that starts a new lap, the cut was
+                // most likely on the run to the line, so it belongs to the
+                // lap just completed.
+                let lap = if current.current_lap_num == previous.current_lap_num {
+                    current
+                } else {
+                    previous
+                };
+                kinds.push((InfringementKind::LapInvalidated, lap));
+            }
+
+            for (kind, lap) in kinds {
+                self.infringements.push(Infringement {
+                    vehicle_index: index as u8,
+                    kind,
+                    lap: lap.current_lap_num,
+                    lap_distance: lap.lap_distance,
+                    session_time,
+                    world_position: self.motion.get(index).map(|motion| motion.world_position),
+                    surface_type: self.telemetry.get(index).map(|data| data.surface_type),
+                });
+            }
+        }
+    }
+
+    /// Renders every infringement grouped by driver.
+    pub fn report(&self) -> String {
+        let mut vehicles: Vec<u8> = self
+            .infringements
+            .iter()
+            .map(|infringement| infringement.vehicle_index)
+            .collect();
+        vehicles.sort_unstable();
+        vehicles.dedup();
+
+        let mut output = String::new();
+        for vehicle_index in vehicles {
+            let infringements: Vec<&Infringement> = self
+                .infringements
+                .iter()
+                .filter(|infringement| infringement.vehicle_index == vehicle_index)
+                .collect();
+            let penalty_seconds: u32 = infringements
+                .iter()
+                .map(|infringement| match infringement.kind {
+                    InfringementKind::Penalty { added, .. } => added as u32,
+                    InfringementKind::LapInvalidated => 0,
+                })
+                .sum();
+            let invalidated = infringements
+                .iter()
+                .filter(|infringement| infringement.kind == InfringementKind::LapInvalidated)
+                .count();
+
+            let name = match self.drivers.get(vehicle_index as usize) {
+                Some(driver) => format!("#{} {}", driver.race_number, driver.name),
+                None => format!("Car {}", vehicle_index),
+            };
+            writeln!(
+                output,
+                "{}: {}s in penalties, {} {} invalidated",
+                name,
+                penalty_seconds,
+                invalidated,
+                if invalidated == 1 { "lap" } else { "laps" }
+            )
+            .unwrap();
+
+            for infringement in infringements {
+                let description = match infringement.kind {
+                    InfringementKind::Penalty { added, total } => {
+                        format!("{}s penalty ({}s total)", added, total)
+                    }
+                    InfringementKind::LapInvalidated => "lap invalidated".to_owned(),
+                };
+                write!(
+                    output,
+                    "  [{}] lap {} at {:.0}m: {}",
+                    format_time(infringement.session_time),
+                    infringement.lap,
+                    infringement.lap_distance,
+                    description
+                )
+                .unwrap();
+                if let Some(wheels) = infringement.wheels_off_track() {
+                    write!(output, ", {} wheels off track", wheels).unwrap();
+                }
+                if let Some(position) = infringement.world_position {
+                    write!(
+                        output,
+                        ", position ({:.1}, {:.1}, {:.1})",
+                        position.x, position.y, position.z
+                    )
+                    .unwrap();
+                }
+                writeln!(output).unwrap();
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{
+        car_motion, car_telemetry, car_telemetry_packet, lap_data, lap_packet, motion_data,
+        motion_packet,
+    };
+
+    #[test]
+    fn test_track_limits() {
+        let mut tracker = StewardingTracker::new();
+        tracker.update(&lap_packet(1, 10.0, 10, vec![lap_data(1, 2, 5500.0)]));
+
+        let mut telemetry = car_telemetry(180, 1.0, 0.0);
+        telemetry.surface_type.rear_left = SurfaceType::Grass;
+        telemetry.surface_type.front_left = SurfaceType::Grass;
+        tracker.update(&car_telemetry_packet(1, 10.5, 11, vec![telemetry]));
+        tracker.update(&motion_packet(
+            1,
+            10.5,
+            11,
+            motion_data(vec![car_motion(120.0, -45.0)]),
+        ));
+
+        let mut invalid = lap_data(1, 2, 5600.0);
+        invalid.current_lap_invalid = LapState::Invalid;
+        invalid.penalties = 3;
+        let new = tracker.update(&lap_packet(1, 11.0, 12, vec![invalid]));
+
+        assert_eq!(new.len(), 2);
+        assert_eq!(
+            new[0].kind,
+            InfringementKind::Penalty { added: 3, total: 3 }
+        );
+        assert_eq!(new[1].kind, InfringementKind::LapInvalidated);
+        assert_eq!(new[1].wheels_off_track(), Some(2));
+
+        assert_eq!(
+            tracker.report(),
+            "Car 0: 3s in penalties, 1 lap invalidated\n  \
+             [0:11.000] lap 2 at 600m: 3s penalty (3s total), 2 wheels off track, position (120.0, 0.0, -45.0)\n  \
+             [0:11.000] lap 2 at 600m: lap invalidated, 2 wheels off track, position (120.0, 0.0, -45.0)\n"
+        );
+    }
+
+    #[test]
+    fn test_invalidation_across_lap_change() {
+        let mut tracker = StewardingTracker::new();
+
+        // Already invalid before the line, so the new lap adds nothing.
+        let mut invalid = lap_data(1, 2, 9000.0);
+        invalid.current_lap_invalid = LapState::Invalid;
+        tracker.update(&lap_packet(1, 10.0, 10, vec![invalid]));
+        invalid = lap_data(1, 3, 10100.0);
+        invalid.current_lap_invalid = LapState::Invalid;
+        assert!(tracker
+            .update(&lap_packet(1, 11.0, 11, vec![invalid]))
+            .is_empty());
+        tracker.update(&lap_packet(1, 12.0, 12, vec![lap_data(1, 3, 10200.0)]));
+
+        // A cut at the last corner shows up with the lap change.
+        tracker.update(&lap_packet(1, 90.0, 90, vec![lap_data(1, 3, 14990.0)]));
+        let mut invalid = lap_data(1, 4, 15010.0);
+        invalid.current_lap_invalid = LapState::Invalid;
+        let new = tracker.update(&lap_packet(1, 91.0, 91, vec![invalid]));
+        assert_eq!(new.len(), 1);
+        assert_eq!(new[0].kind, InfringementKind::LapInvalidated);
+        assert_eq!((new[0].lap, new[0].lap_distance), (3, 4990.0));
+    }
+}
@@ -0,0 +1,727 @@
+use crate::mappings::{PitStatus, TrackId, TyreCompound};
+use crate::{Telemetry, TelemetryData};
+
+/// Lap time added by each kilogram of fuel, when nothing better is known.
+pub const DEFAULT_FUEL_EFFECT: f32 = 0.03;
+
+/// Typical time lost driving through the pit lane and stopping, in seconds.
+pub fn pit_loss(track_id: TrackId) -> f32 {
+    match track_id {
+        TrackId::Melbourne => 22.0,
+        TrackId::PaulRicard => 21.0,
+        TrackId::Shanghai => 22.0,
+        TrackId::Sakhir | TrackId::SakhirShort => 22.0,
+        TrackId::Catalunya => 22.0,
+        TrackId::Monaco => 19.0,
+        TrackId::Montreal => 18.0,
+        TrackId::Silverstone | TrackId::SilverstoneShort => 20.0,
+        TrackId::Hockenheim => 17.0,
+        TrackId::Hungaroring => 20.0,
+        TrackId::Spa => 19.0,
+        TrackId::Monza => 23.0,
+        TrackId::Singapore => 28.0,
+        TrackId::Suzuka | TrackId::SuzukaShort => 22.0,
+        TrackId::AbuDhabi => 21.0,
+        TrackId::Texas | TrackId::TexasShort => 21.0,
+        TrackId::Brazil => 21.0,
+        TrackId::Austria => 20.0,
+        TrackId::Sochi => 25.0,
+        TrackId::Mexico => 22.0,
+        TrackId::Baku => 20.0,
+        TrackId::Unknown => 22.0,
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CompoundModel {
+    pub compound: TyreCompound,
+    /// Lap time on a new tyre with an empty tank.
+    pub pace: f32,
+    /// Time lost per lap of tyre age.
+    pub degradation: f32,
+    /// Longest stint the tyre can do.
+    pub max_laps: u8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrategyModel {
+    pub compounds: Vec<CompoundModel>,
+    pub pit_loss: f32,
+    /// Lap time added by each kilogram of fuel.
+    pub fuel_effect: f32,
+    /// Fuel burnt per lap, in kilograms.
+    pub fuel_per_lap: f32,
+    /// Time lost on the lap out of the pits while the new tyres warm up.
+    pub out_lap_penalty: f32,
+    /// Dry races must use at least two different compounds.
+    pub require_two_compounds: bool,
+}
+
+impl StrategyModel {
+    pub fn new(track_id: TrackId, compounds: Vec<CompoundModel>) -> Self {
+        StrategyModel {
+            compounds,
+            pit_loss: pit_loss(track_id),
+            fuel_effect: DEFAULT_FUEL_EFFECT,
+            fuel_per_lap: 1.6,
+            out_lap_penalty: 1.0,
+            require_two_compounds: true,
+        }
+    }
+
+    pub fn compound(&self, compound: TyreCompound) -> Option<&CompoundModel> {
+        self.compounds
+            .iter()
+            .find(|model| model.compound == compound)
+    }
+
+    /// Predicted time of a lap started with `tyre_age` laps on the tyres and
+    /// fuel for `fuel_laps` more laps.
+    pub fn lap_time(&self, compound: TyreCompound, tyre_age: u8, fuel_laps: u8) -> Option<f32> {
+        let model = self.compound(compound)?;
+        Some(
+            model.pace
+                + model.degradation * tyre_age as f32
+                + self.fuel_effect * self.fuel_per_lap * fuel_laps as f32,
+        )
+    }
+
+    fn stint_time(
+        &self,
+        compound: TyreCompound,
+        tyre_age: u8,
+        laps: u8,
+        fuel_laps: u8,
+    ) -> Option<f32> {
+        let model = self.compound(compound)?;
+        if tyre_age as u16 + laps as u16 > model.max_laps as u16 {
+            return None;
+        }
+
+        (0..laps).try_fold(0.0, |total, lap| {
+            Some(total + self.lap_time(compound, tyre_age + lap, fuel_laps - lap)?)
+        })
+    }
+
+    /// Times of stints of 1, 2, ... laps with fuel for `fuel_laps` laps,
+    /// stopping where the tyres give out or the fuel runs out.
+    fn stint_times(&self, compound: TyreCompound, tyre_age: u8, fuel_laps: u8) -> Vec<f32> {
+        let max_laps = match self.compound(compound) {
+            Some(model) => model.max_laps,
+            None => return Vec::new(),
+        };
+
+        let mut total = 0.0;
+        (0..fuel_laps)
+            .take_while(|lap| (tyre_age as u16 + *lap as u16) < max_laps as u16)
+            .filter_map(|lap| {
+                total += self.lap_time(compound, tyre_age + lap, fuel_laps - lap)?;
+                Some(total)
+            })
+            .collect()
+    }
+}
+
+/// The tyres a car is currently running.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CarState {
+    pub compound: TyreCompound,
+    /// Laps completed on these tyres.
+    pub tyre_age: u8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RaceState {
+    pub car: CarState,
+    pub laps_remaining: u8,
+    /// Compounds already raced on, including the current one.
+    pub used_compounds: Vec<TyreCompound>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Stint {
+    pub compound: TyreCompound,
+    pub laps: u8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Strategy {
+    /// The first stint carries on with the current tyres.
+    pub stints: Vec<Stint>,
+    /// Predicted time for the rest of the race.
+    pub time: f32,
+}
+
+impl Strategy {
+    pub fn stops(&self) -> usize {
+        self.stints.len().saturating_sub(1)
+    }
+
+    /// Laps on which to pit, counted from `current_lap`.
+    pub fn pit_laps(&self, current_lap: u8) -> Vec<u8> {
+        let mut lap = current_lap;
+        self.stints[..self.stops()]
+            .iter()
+            .map(|stint| {
+                lap = lap.saturating_add(stint.laps);
+                lap.saturating_sub(1)
+            })
+            .collect()
+    }
+}
+
+/// Time for the rest of the race with the given stints, or `None` if the
+/// plan is not possible.
+pub fn evaluate(model: &StrategyModel, state: &RaceState, stints: &[Stint]) -> Option<f32> {
+    let (first, rest) = stints.split_first()?;
+    if first.compound != state.car.compound
+        || stints.iter().map(|stint| stint.laps as u16).sum::<u16>() != state.laps_remaining as u16
+        || stints.iter().any(|stint| stint.laps == 0)
+    {
+        return None;
+    }
+
+    if model.require_two_compounds {
+        let mut compounds = state.used_compounds.clone();
+        compounds.extend(stints.iter().map(|stint| stint.compound));
+        if !compound_rule_met(&compounds) {
+            return None;
+        }
+    }
+
+    let mut fuel_laps = state.laps_remaining;
+    let mut time = model.stint_time(first.compound, state.car.tyre_age, first.laps, fuel_laps)?;
+    fuel_laps -= first.laps;
+    for stint in rest {
+        time += model.pit_loss + model.out_lap_penalty;
+        time += model.stint_time(stint.compound, 0, stint.laps, fuel_laps)?;
+        fuel_laps -= stint.laps;
+    }
+    Some(time)
+}
+
+/// Whether a dry race run on `compounds` used two different dry compounds,
+/// which wet tyres waive.
+fn compound_rule_met(compounds: &[TyreCompound]) -> bool {
+    let dry: Vec<_> = compounds.iter().filter(|c| !c.is_wet()).collect();
+    dry.is_empty() || dry.len() < compounds.len() || dry.iter().any(|c| *c != dry[0])
+}
+
+/// Fastest way found to a point of the race, and the stint that led there.
+#[derive(Debug, Copy, Clone)]
+struct Step {
+    time: f32,
+    /// Point the stint started from, `None` for the first stint.
+    from: Option<usize>,
+    stint: Stint,
+}
+
+fn offer(slot: &mut Option<Step>, step: Step) {
+    if step.time.is_finite() && !matches!(slot, Some(best) if best.time <= step.time) {
+        *slot = Some(step);
+    }
+}
+
+/// The fastest valid plan for each number of stops up to `max_stops` and
+/// each final compound, fastest first.
+///
+/// Plans are built lap by lap, keeping only the fastest way to reach each
+/// lap with a given number of stops, compound fitted and whether the two
+/// compound rule is met. The work grows with the number of stops, the
+/// square of the compounds and the square of the laps remaining.
+pub fn rank_strategies(
+    model: &StrategyModel,
+    state: &RaceState,
+    max_stops: usize,
+) -> Vec<Strategy> {
+    let laps = state.laps_remaining as usize;
+    let compounds: Vec<TyreCompound> = model.compounds.iter().map(|model| model.compound).collect();
+    let count = compounds.len();
+    let current = match compounds.iter().position(|c| *c == state.car.compound) {
+        Some(current) => current,
+        None => return Vec::new(),
+    };
+    // Every stint needs at least one lap.
+    let max_stops = max_stops.min(laps.saturating_sub(1));
+    let index = |lap: usize, stops: usize, compound: usize, met: bool| {
+        ((lap * (max_stops + 1) + stops) * count + compound) * 2 + met as usize
+    };
+
+    let mut used = state.used_compounds.clone();
+    used.push(state.car.compound);
+    let met = !model.require_two_compounds || compound_rule_met(&used);
+
+    let mut steps: Vec<Option<Step>> = vec![None; (laps + 1) * (max_stops + 1) * count * 2];
+    let first = model.stint_times(state.car.compound, state.car.tyre_age, laps as u8);
+    for (lap, time) in first.into_iter().enumerate() {
+        let stint = Stint {
+            compound: state.car.compound,
+            laps: lap as u8 + 1,
+        };
+        let step = Step {
+            time,
+            from: None,
+            stint,
+        };
+        offer(&mut steps[index(lap + 1, 0, current, met)], step);
+    }
+
+    // Stints only move forward, so every point is final before it is left.
+    for from in 0..steps.len() {
+        let step = match steps[from] {
+            Some(step) => step,
+            None => continue,
+        };
+        let met = from % 2 == 1;
+        let compound = from / 2 % count;
+        let stops = from / 2 / count % (max_stops + 1);
+        let lap = from / 2 / count / (max_stops + 1);
+        if stops == max_stops || lap == laps {
+            continue;
+        }
+
+        let fuel_laps = (laps - lap) as u8;
+        for (next, &next_compound) in compounds.iter().enumerate() {
+            // While the rule is unmet every stint so far was on one dry compound.
+            let next_met = met || next != compound || next_compound.is_wet();
+            let stopped = step.time + model.pit_loss + model.out_lap_penalty;
+            for (stint_laps, time) in model
+                .stint_times(next_compound, 0, fuel_laps)
+                .into_iter()
+                .enumerate()
+            {
+                let stint = Stint {
+                    compound: next_compound,
+                    laps: stint_laps as u8 + 1,
+                };
+                let next_step = Step {
+                    time: stopped + time,
+                    from: Some(from),
+                    stint,
+                };
+                let end = lap + stint_laps + 1;
+                offer(&mut steps[index(end, stops + 1, next, next_met)], next_step);
+            }
+        }
+    }
+
+    let mut strategies = Vec::new();
+    for stops in 0..=max_stops {
+        for compound in 0..count {
+            let mut at = Some(index(laps, stops, compound, true));
+            let mut stints = Vec::new();
+            while let Some(step) = at.and_then(|at| steps[at]) {
+                stints.push(step.stint);
+                at = step.from;
+            }
+            stints.reverse();
+            if let Some(time) = evaluate(model, state, &stints) {
+                strategies.push(Strategy { stints, time });
+            }
+        }
+    }
+
+    strategies.retain(|strategy| strategy.time.is_finite());
+    strategies.sort_by(|a, b| a.time.total_cmp(&b.time));
+    strategies
+}
+
+/// Outcome of pitting at a different time to a rival.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PitBattle {
+    /// Time made up on the rival while on different tyres.
+    pub gain: f32,
+    /// Gap to the rival ahead before the stops.
+    pub gap: f32,
+}
+
+impl PitBattle {
+    pub fn succeeds(&self) -> bool {
+        self.gain > self.gap
+    }
+}
+
+/// Pitting `laps` laps before the rival ahead, who is `gap` seconds up the
+/// road. Fuel is the same for both cars and cancels out.
+pub fn undercut(
+    model: &StrategyModel,
+    rival: CarState,
+    gap: f32,
+    compound: TyreCompound,
+    laps: u8,
+) -> Option<PitBattle> {
+    let mut gain = -model.out_lap_penalty;
+    for lap in 0..laps {
+        gain += model.lap_time(rival.compound, rival.tyre_age.saturating_add(lap), 0)?
+            - model.lap_time(compound, lap, 0)?;
+    }
+    Some(PitBattle { gain, gap })
+}
+
+/// Staying out `laps` laps longer than the rival ahead, who pits onto
+/// `compound` first.
+pub fn overcut(
+    model: &StrategyModel,
+    player: CarState,
+    gap: f32,
+    compound: TyreCompound,
+    laps: u8,
+) -> Option<PitBattle> {
+    let mut gain = model.out_lap_penalty;
+    for lap in 0..laps {
+        gain += model.lap_time(compound, lap, 0)?
+            - model.lap_time(player.compound, player.tyre_age.saturating_add(lap), 0)?;
+    }
+    Some(PitBattle { gain, gap })
+}
+
+/// A completed lap and the tyres and fuel it was driven on.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StintLap {
+    pub lap: u8,
+    pub lap_time: f32,
+    pub compound: TyreCompound,
+    /// Laps already completed on the tyres when this lap started.
+    pub tyre_age: u8,
+    /// Mean wear of the four tyres at the end of the lap, in percent.
+    pub tyre_wear: f32,
+    pub fuel_in_tank: f32,
+    /// The car went through the pit lane on this lap.
+    pub pit: bool,
+}
+
+#[derive(Debug, Default)]
+struct CarStints {
+    compound: Option<TyreCompound>,
+    used_compounds: Vec<TyreCompound>,
+    tyre_age: u8,
+    tyre_wear: f32,
+    fuel_in_tank: f32,
+    lap: Option<u8>,
+    in_pits: bool,
+    pitted: bool,
+    /// Tyres the car entered the pits on, which the in lap is counted against.
+    in_lap: Option<(TyreCompound, u8)>,
+    laps: Vec<StintLap>,
+}
+
+/// Follows every car's tyres and lap times to feed the strategy model.
+#[derive(Debug, Default)]
+pub struct StintTracker {
+    cars: Vec<CarStints>,
+}
+
+impl StintTracker {
+    pub fn new() -> Self {
+        StintTracker::default()
+    }
+
+    pub fn car_state(&self, vehicle_index: u8) -> Option<CarState> {
+        let car = self.cars.get(vehicle_index as usize)?;
+        Some(CarState {
+            compound: car.compound?,
+            tyre_age: car.tyre_age,
+        })
+    }
+
+    pub fn used_compounds(&self, vehicle_index: u8) -> &[TyreCompound] {
+        self.cars
+            .get(vehicle_index as usize)
+            .map_or(&[], |car| &car.used_compounds[..])
+    }
+
+    pub fn laps(&self, vehicle_index: u8) -> &[StintLap] {
+        self.cars
+            .get(vehicle_index as usize)
+            .map_or(&[], |car| &car.laps[..])
+    }
+
+    fn car(&mut self, vehicle_index: usize) -> &mut CarStints {
+        if self.cars.len() <= vehicle_index {
+            self.cars.resize_with(vehicle_index + 1, CarStints::default);
+        }
+        &mut self.cars[vehicle_index]
+    }
+
+    pub fn update(&mut self, packet: &Telemetry) {
+        match packet.data {
+            TelemetryData::CarStatus(ref data) => {
+                for (index, status) in data.car_status_data.iter().enumerate() {
+                    let car = self.car(index);
+                    let compound = status.actual_tyre_compound;
+                    if car.compound.is_some() && car.compound != Some(compound) {
+                        car.tyre_age = 0;
+                    }
+                    if !car.used_compounds.contains(&compound) {
+                        car.used_compounds.push(compound);
+                    }
+                    car.compound = Some(compound);
+                    let wear = status.tyres_wear.to_array();
+                    car.tyre_wear = wear.iter().map(|&w| w as f32).sum::<f32>() / 4.0;
+                    car.fuel_in_tank = status.fuel_in_tank;
+                }
+            }
+            TelemetryData::Lap(ref data) => {
+                for (index, lap) in data.lap_data.iter().enumerate() {
+                    let car = self.car(index);
+                    let in_pits = lap.pit_status != PitStatus::None;
+                    if in_pits {
+                        if !car.in_pits {
+                            car.in_lap = car.compound.map(|compound| (compound, car.tyre_age));
+                        }
+                        car.pitted = true;
+                    } else if car.in_pits {
+                        // Leaving the pit lane on new tyres.
+                        car.tyre_age = 0;
+                    }
+                    car.in_pits = in_pits;
+
+                    let previous = car.lap.replace(lap.current_lap_num);
+                    let completed = match previous {
+                        Some(previous) if lap.current_lap_num == previous + 1 => previous,
+                        _ => continue,
+                    };
+                    let tyres = car
+                        .in_lap
+                        .take()
+                        .or_else(|| car.compound.map(|compound| (compound, car.tyre_age)));
+                    if let Some((compound, tyre_age)) = tyres {
+                        car.laps.push(StintLap {
+                            lap: completed,
+                            lap_time: lap.last_lap_time,
+                            compound,
+                            tyre_age,
+                            tyre_wear: car.tyre_wear,
+                            fuel_in_tank: car.fuel_in_tank,
+                            pit: car.pitted,
+                        });
+                    }
+                    car.pitted = in_pits;
+                    car.tyre_age = car.tyre_age.saturating_add(1);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{
+        car_status, car_status_packet, compound, lap_data, lap_packet,
+    };
+
+    fn model() -> StrategyModel {
+        let mut model = StrategyModel::new(
+            TrackId::Monza,
+            vec![
+                CompoundModel {
+                    compound: compound(16),
+                    pace: 80.0,
+                    degradation: 0.3,
+                    max_laps: 15,
+                },
+                CompoundModel {
+                    compound: compound(18),
+                    pace: 80.8,
+                    degradation: 0.1,
+                    max_laps: 30,
+                },
+            ],
+        );
+        model.fuel_effect = 0.0;
+        model
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let model = model();
+        let state = RaceState {
+            car: CarState {
+                compound: compound(16),
+                tyre_age: 0,
+            },
+            laps_remaining: 20,
+            used_compounds: vec![compound(16)],
+        };
+        let soft = |laps| Stint {
+            compound: compound(16),
+            laps,
+        };
+        let medium = |laps| Stint {
+            compound: compound(18),
+            laps,
+        };
+
+        // Too long on softs, and only one compound.
+        assert_eq!(evaluate(&model, &state, &[soft(20)]), None);
+        assert_eq!(evaluate(&model, &state, &[soft(10), soft(10)]), None);
+        assert_eq!(evaluate(&model, &state, &[soft(10), medium(5)]), None);
+
+        let time = evaluate(&model, &state, &[soft(10), medium(10)]).unwrap();
+        let expected = 10.0 * 80.0 + 0.3 * 45.0 + 23.0 + 1.0 + 10.0 * 80.8 + 0.1 * 45.0;
+        assert!((time - expected).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_rank_strategies() {
+        let model = model();
+        let state = RaceState {
+            car: CarState {
+                compound: compound(16),
+                tyre_age: 5,
+            },
+            laps_remaining: 30,
+            used_compounds: vec![compound(16)],
+        };
+
+        let strategies = rank_strategies(&model, &state, 2);
+        assert!(!strategies.is_empty());
+        assert!(strategies
+            .windows(2)
+            .all(|pair| pair[0].time <= pair[1].time));
+
+        let best = &strategies[0];
+        assert_eq!(best.stops(), 1);
+        assert_eq!(best.stints[1].compound, compound(18));
+        assert_eq!(best.stints.iter().map(|stint| stint.laps).sum::<u8>(), 30);
+        assert_eq!(best.pit_laps(11), vec![11 + best.stints[0].laps - 1]);
+
+        // The best split of soft then medium, found by trying every one.
+        let fastest = (1..30)
+            .filter_map(|laps| {
+                let stints = [
+                    Stint {
+                        compound: compound(16),
+                        laps,
+                    },
+                    Stint {
+                        compound: compound(18),
+                        laps: 30 - laps,
+                    },
+                ];
+                evaluate(&model, &state, &stints)
+            })
+            .min_by(|a, b| a.total_cmp(b));
+        assert_eq!(Some(best.time), fastest);
+
+        let orders: Vec<Vec<TyreCompound>> = strategies
+            .iter()
+            .map(|strategy| strategy.stints.iter().map(|stint| stint.compound).collect())
+            .collect();
+        assert!(orders
+            .iter()
+            .enumerate()
+            .all(|(index, order)| !orders[..index].contains(order)));
+    }
+
+    #[test]
+    fn test_rank_long_race() {
+        let model = model();
+        let state = RaceState {
+            car: CarState {
+                compound: compound(18),
+                tyre_age: 0,
+            },
+            laps_remaining: 70,
+            used_compounds: vec![compound(18)],
+        };
+
+        let strategies = rank_strategies(&model, &state, 4);
+        assert!(strategies[0].stops() >= 2);
+        assert_eq!(
+            strategies[0]
+                .stints
+                .iter()
+                .map(|stint| stint.laps as u16)
+                .sum::<u16>(),
+            70
+        );
+    }
+
+    #[test]
+    fn test_rank_many_stops() {
+        let mut model = model();
+        for &(id, pace, degradation, max_laps) in &[(17, 80.4, 0.2, 20), (19, 81.2, 0.05, 40)] {
+            model.compounds.push(CompoundModel {
+                compound: compound(id),
+                pace,
+                degradation,
+                max_laps,
+            });
+        }
+        let state = RaceState {
+            car: CarState {
+                compound: compound(18),
+                tyre_age: 3,
+            },
+            laps_remaining: 60,
+            used_compounds: vec![compound(18)],
+        };
+
+        let strategies = rank_strategies(&model, &state, 6);
+        assert!(strategies.len() <= 7 * 4);
+        assert!(strategies.iter().all(|strategy| strategy.stops() <= 6));
+        let fewer = rank_strategies(&model, &state, 2);
+        assert!(strategies[0].time <= fewer[0].time);
+        assert_eq!(
+            evaluate(&model, &state, &strategies[0].stints),
+            Some(strategies[0].time)
+        );
+    }
+
+    #[test]
+    fn test_undercut_and_overcut() {
+        let model = model();
+        let worn = CarState {
+            compound: compound(16),
+            tyre_age: 14,
+        };
+        let battle = undercut(&model, worn, 1.5, compound(18), 2).unwrap();
+        // Two laps of 84.2 and 84.5 against 80.8 and 80.9, less the out lap.
+        assert!((battle.gain - 6.0).abs() < 1e-3);
+        assert!(battle.succeeds());
+
+        let battle = overcut(&model, worn, 1.0, compound(18), 1).unwrap();
+        assert!(!battle.succeeds());
+    }
+
+    #[test]
+    fn test_stint_tracker() {
+        let mut tracker = StintTracker::new();
+        let mut status = car_status(compound(16));
+        tracker.update(&car_status_packet(1, 0.0, 0, vec![status]));
+        tracker.update(&lap_packet(1, 0.0, 0, vec![lap_data(1, 1, 0.0)]));
+
+        let mut lap = lap_data(1, 2, 5000.0);
+        lap.last_lap_time = 81.0;
+        tracker.update(&lap_packet(1, 81.0, 1, vec![lap]));
+        lap.pit_status = PitStatus::Pitting;
+        tracker.update(&lap_packet(1, 150.0, 2, vec![lap]));
+
+        status.actual_tyre_compound = compound(18);
+        tracker.update(&car_status_packet(1, 160.0, 3, vec![status]));
+        let mut lap = lap_data(1, 3, 10000.0);
+        lap.last_lap_time = 100.0;
+        lap.pit_status = PitStatus::InPitArea;
+        tracker.update(&lap_packet(1, 181.0, 4, vec![lap]));
+        lap.pit_status = PitStatus::None;
+        tracker.update(&lap_packet(1, 185.0, 5, vec![lap]));
+
+        let laps = tracker.laps(0);
+        assert_eq!(laps.len(), 2);
+        assert_eq!((laps[0].lap, laps[0].tyre_age, laps[0].pit), (1, 0, false));
+        // The in lap is counted against the tyres the car came in on.
+        assert_eq!((laps[1].lap, laps[1].tyre_age, laps[1].pit), (2, 1, true));
+        assert_eq!(laps[1].compound, compound(16));
+        assert_eq!(
+            tracker.car_state(0),
+            Some(CarState {
+                compound: compound(18),
+                tyre_age: 0,
+            })
+        );
+        assert_eq!(tracker.used_compounds(0), &[compound(16), compound(18)]);
+    }
+}
@@ -0,0 +1,421 @@
+use crate::analysis::setups::SetupField;
+use crate::analysis::stats::{pearson, Summary};
+use crate::packets::{CarSetupData, CarTelemetryData, MotionData};
+use crate::{Telemetry, TelemetryData, Wheel, WheelData};
+
+/// Counts of values in equally sized bins between `min` and `max`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub min: f32,
+    pub bin_width: f32,
+    pub counts: Vec<u32>,
+    /// Values smaller than `min`.
+    pub below: u32,
+    /// Values larger than the last bin.
+    pub above: u32,
+}
+
+impl Histogram {
+    /// Bins of `bin_width` from `min` up to at least `max`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bin_width` is not a positive, finite number.
+    pub fn new(min: f32, max: f32, bin_width: f32) -> Self {
+        assert!(
+            bin_width > 0.0 && bin_width.is_finite(),
+            "histogram bin width must be positive and finite, got {}",
+            bin_width
+        );
+        let bins = ((max - min) / bin_width).ceil().max(1.0) as usize;
+        Histogram {
+            min,
+            bin_width,
+            counts: vec![0; bins],
+            below: 0,
+            above: 0,
+        }
+    }
+
+    pub fn add(&mut self, value: f32) {
+        if value < self.min {
+            self.below += 1;
+            return;
+        }
+
+        let bin = ((value - self.min) / self.bin_width) as usize;
+        match self.counts.get_mut(bin) {
+            Some(count) => *count += 1,
+            None => self.above += 1,
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.counts.iter().sum::<u32>() + self.below + self.above
+    }
+
+    /// Lower and upper bound of a bin.
+    pub fn bin_range(&self, bin: usize) -> (f32, f32) {
+        let start = self.min + bin as f32 * self.bin_width;
+        (start, start + self.bin_width)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SuspensionConfig {
+    /// Range and bin width of the suspension velocity histograms. The bin
+    /// width must be positive.
+    pub velocity_min: f32,
+    pub velocity_max: f32,
+    pub velocity_bin_width: f32,
+    /// Suspension position at which a wheel is considered to have run out
+    /// of travel.
+    pub bottoming_position: f32,
+    /// Pedal travel, between 0 and 1, that counts as braking or accelerating.
+    pub pedal_threshold: f32,
+    /// Steering input, between -1 and 1, that counts as cornering.
+    pub steer_threshold: f32,
+}
+
+impl Default for SuspensionConfig {
+    fn default() -> Self {
+        SuspensionConfig {
+            velocity_min: -300.0,
+            velocity_max: 300.0,
+            velocity_bin_width: 25.0,
+            bottoming_position: 60.0,
+            pedal_threshold: 0.1,
+            steer_threshold: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CornerPhase {
+    Straight,
+    Braking,
+    MidCorner,
+    Exit,
+}
+
+impl CornerPhase {
+    pub const ALL: [CornerPhase; 4] = [
+        CornerPhase::Straight,
+        CornerPhase::Braking,
+        CornerPhase::MidCorner,
+        CornerPhase::Exit,
+    ];
+
+    fn from_inputs(telemetry: &CarTelemetryData, config: &SuspensionConfig) -> Self {
+        if telemetry.brake >= config.pedal_threshold {
+            CornerPhase::Braking
+        } else if telemetry.steer.abs() < config.steer_threshold {
+            CornerPhase::Straight
+        } else if telemetry.throttle >= config.pedal_threshold {
+            CornerPhase::Exit
+        } else {
+            CornerPhase::MidCorner
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Pitch and roll estimated from the difference in suspension travel
+/// between the front and rear, and the left and right, wheels.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Attitude {
+    pub pitch: Summary,
+    pub roll: Summary,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuspensionStats {
+    pub velocity: WheelData<Histogram>,
+    pub position: WheelData<Summary>,
+    /// Number of times each wheel ran out of travel.
+    pub bottoming: WheelData<u32>,
+    phases: [Attitude; 4],
+}
+
+impl SuspensionStats {
+    fn new(config: &SuspensionConfig) -> Self {
+        let histogram = Histogram::new(
+            config.velocity_min,
+            config.velocity_max,
+            config.velocity_bin_width,
+        );
+        SuspensionStats {
+            velocity: WheelData {
+                rear_left: histogram.clone(),
+                rear_right: histogram.clone(),
+                front_left: histogram.clone(),
+                front_right: histogram,
+            },
+            position: WheelData::default(),
+            bottoming: WheelData::default(),
+            phases: [Attitude::default(); 4],
+        }
+    }
+
+    pub fn attitude(&self, phase: CornerPhase) -> &Attitude {
+        &self.phases[phase.index()]
+    }
+
+    pub fn total_bottoming(&self) -> u32 {
+        self.bottoming.to_array().iter().sum()
+    }
+}
+
+/// Suspension statistics gathered while running one setup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetupRun {
+    pub setup: Option<CarSetupData>,
+    pub stats: SuspensionStats,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Bottoming {
+    pub wheel: Wheel,
+    pub lap: u8,
+    pub lap_distance: f32,
+    pub session_time: f32,
+    pub position: f32,
+}
+
+/// Damper, bottoming and attitude analysis of the player car, split by
+/// setup.
+#[derive(Debug)]
+pub struct SuspensionAnalyzer {
+    config: SuspensionConfig,
+    lap: u8,
+    lap_distance: f32,
+    phase: CornerPhase,
+    bottomed: WheelData<bool>,
+    runs: Vec<SetupRun>,
+}
+
+impl SuspensionAnalyzer {
+    pub fn new(config: SuspensionConfig) -> Self {
+        SuspensionAnalyzer {
+            config,
+            lap: 0,
+            lap_distance: 0.0,
+            phase: CornerPhase::Straight,
+            bottomed: WheelData::default(),
+            runs: vec![SetupRun {
+                setup: None,
+                stats: SuspensionStats::new(&config),
+            }],
+        }
+    }
+
+    pub fn runs(&self) -> &[SetupRun] {
+        &self.runs
+    }
+
+    /// Returns the wheels that bottomed out in this packet.
+    pub fn update(&mut self, packet: &Telemetry) -> Vec<Bottoming> {
+        let player = packet.header.player_car_index as usize;
+
+        match packet.data {
+            TelemetryData::Lap(ref data) => {
+                if let Some(lap) = data.lap_data.get(player) {
+                    self.lap = lap.current_lap_num;
+                    self.lap_distance = lap.lap_distance;
+                }
+            }
+            TelemetryData::CarTelemetry(ref data) => {
+                if let Some(telemetry) = data.car_telemetry_data.get(player) {
+                    self.phase = CornerPhase::from_inputs(telemetry, &self.config);
+                }
+            }
+            TelemetryData::CarSetups(ref data) => {
+                if let Some(setup) = data.car_setups.get(player) {
+                    self.update_setup(*setup);
+                }
+            }
+            TelemetryData::Motion(ref data) => {
+                return self.update_motion(packet.header.session_time, data)
+            }
+            _ => {}
+        }
+
+        Vec::new()
+    }
+
+    fn update_setup(&mut self, setup: CarSetupData) {
+        let run = self.runs.last_mut().unwrap();
+        if run.setup == Some(setup) {
+            return;
+        }
+
+        if run.stats.position.rear_left.count == 0 {
+            run.setup = Some(setup);
+        } else {
+            self.runs.push(SetupRun {
+                setup: Some(setup),
+                stats: SuspensionStats::new(&self.config),
+            });
+        }
+    }
+
+    fn update_motion(&mut self, session_time: f32, data: &MotionData) -> Vec<Bottoming> {
+        let stats = &mut self.runs.last_mut().unwrap().stats;
+        let mut bottoming = Vec::new();
+
+        for &wheel in Wheel::ALL.iter() {
+            let position = *data.suspension_position.get(wheel);
+            stats.position.get_mut(wheel).add(position);
+            stats
+                .velocity
+                .get_mut(wheel)
+                .add(*data.suspension_velocity.get(wheel));
+
+            let bottomed = position >= self.config.bottoming_position;
+            let was_bottomed = std::mem::replace(self.bottomed.get_mut(wheel), bottomed);
+            if bottomed && !was_bottomed {
+                *stats.bottoming.get_mut(wheel) += 1;
+                bottoming.push(Bottoming {
+                    wheel,
+                    lap: self.lap,
+                    lap_distance: self.lap_distance,
+                    session_time,
+                    position,
+                });
+            }
+        }
+
+        let position = &data.suspension_position;
+        let attitude = &mut stats.phases[self.phase.index()];
+        attitude.pitch.add(
+            (position.front_left + position.front_right - position.rear_left - position.rear_right)
+                / 2.0,
+        );
+        attitude.roll.add(
+            (position.front_left + position.rear_left - position.front_right - position.rear_right)
+                / 2.0,
+        );
+
+        bottoming
+    }
+}
+
+impl Default for SuspensionAnalyzer {
+    fn default() -> Self {
+        SuspensionAnalyzer::new(SuspensionConfig::default())
+    }
+}
+
+/// Pearson correlation between a setup value and a statistic over several
+/// runs, e.g. ride height against bottoming.
+///
+/// Returns `None` with fewer than two usable runs or when either side never
+/// changes.
+pub fn correlate<F>(runs: &[SetupRun], field: &SetupField, metric: F) -> Option<f32>
+where
+    F: Fn(&SuspensionStats) -> Option<f32>,
+{
+    let points: Vec<(f32, f32)> = runs
+        .iter()
+        .filter_map(|run| Some((field.get(run.setup.as_ref()?), metric(&run.stats)?)))
+        .collect();
+    pearson(&points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{
+        car_motion, car_setup, car_setups_packet, car_telemetry, car_telemetry_packet, motion_data,
+        motion_packet, wheels,
+    };
+    use crate::analysis::SETUP_FIELDS;
+
+    fn motion(position: WheelData<f32>, velocity: f32) -> Telemetry<'static> {
+        let mut data = motion_data(vec![car_motion(0.0, 0.0)]);
+        data.suspension_position = position;
+        data.suspension_velocity = wheels(velocity);
+        motion_packet(1, 0.0, 0, data)
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::new(-50.0, 50.0, 25.0);
+        for value in [-60.0, -50.0, -1.0, 0.0, 49.0, 50.0].iter() {
+            histogram.add(*value);
+        }
+        assert_eq!(histogram.counts, vec![1, 1, 1, 1]);
+        assert_eq!((histogram.below, histogram.above), (1, 1));
+        assert_eq!(histogram.total(), 6);
+        assert_eq!(histogram.bin_range(2), (0.0, 25.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "histogram bin width must be positive")]
+    fn test_histogram_rejects_zero_width() {
+        Histogram::new(-50.0, 50.0, 0.0);
+    }
+
+    #[test]
+    fn test_bottoming_and_attitude() {
+        let mut analyzer = SuspensionAnalyzer::default();
+        let mut braking = car_telemetry(250, 0.0, 1.0);
+        braking.steer = 0.0;
+        analyzer.update(&car_telemetry_packet(1, 0.0, 0, vec![braking]));
+
+        let mut position = wheels(20.0);
+        position.front_left = 65.0;
+        position.front_right = 45.0;
+        let bottoming = analyzer.update(&motion(position, 100.0));
+        assert_eq!(bottoming.len(), 1);
+        assert_eq!(bottoming[0].wheel, Wheel::FrontLeft);
+        assert!(analyzer.update(&motion(position, -100.0)).is_empty());
+        analyzer.update(&motion(wheels(20.0), 0.0));
+        assert_eq!(analyzer.update(&motion(position, 0.0)).len(), 1);
+
+        let stats = &analyzer.runs()[0].stats;
+        assert_eq!(stats.bottoming.front_left, 2);
+        assert_eq!(stats.total_bottoming(), 2);
+        assert_eq!(stats.velocity.rear_left.counts[16], 1);
+        assert_eq!(stats.velocity.rear_left.counts[8], 1);
+
+        let attitude = stats.attitude(CornerPhase::Braking);
+        assert_eq!(attitude.pitch.count, 4);
+        assert_eq!(attitude.pitch.max, 35.0);
+        assert_eq!(attitude.roll.max, 10.0);
+        assert_eq!(stats.attitude(CornerPhase::Straight).pitch.count, 0);
+    }
+
+    #[test]
+    fn test_runs_per_setup_and_correlation() {
+        let mut analyzer = SuspensionAnalyzer::default();
+        let height = SETUP_FIELDS
+            .iter()
+            .find(|field| field.name == "front_suspension_height")
+            .unwrap();
+
+        for (ride_height, bottoming) in [(2, 3), (4, 1), (6, 0)].iter() {
+            let mut setup = car_setup();
+            setup.front_suspension_height = *ride_height;
+            analyzer.update(&car_setups_packet(1, vec![setup]));
+            for _ in 0..*bottoming {
+                analyzer.update(&motion(wheels(70.0), 0.0));
+                analyzer.update(&motion(wheels(0.0), 0.0));
+            }
+            analyzer.update(&motion(wheels(0.0), 0.0));
+        }
+
+        let runs = analyzer.runs();
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[0].setup.unwrap().front_suspension_height, 2);
+        assert_eq!(runs[0].stats.bottoming.front_left, 3);
+
+        let correlation =
+            correlate(runs, height, |stats| Some(stats.total_bottoming() as f32)).unwrap();
+        assert!(correlation < -0.9);
+        assert_eq!(correlate(&runs[..1], height, |_| Some(1.0)), None);
+    }
+}
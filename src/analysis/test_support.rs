@@ -0,0 +1,332 @@
+use std::convert::TryFrom;
+
+use crate::mappings::{
+    AntiLockBrakes, DrsAllowed, ErsDeployMode, FiaFlag, FuelMix, TractionControl, TyreCompound,
+    VisualCompound,
+};
+use crate::mappings::{
+    DriverId, DriverStatus, Formula, LapState, Nationality, NetworkGame, PitStatus, ResultStatus,
+    SafetyCarStatus, Sector, SessionType, SurfaceType, TeamId, TrackId, VehicleController, Weather,
+};
+use crate::packets::car_status::CarStatusData;
+use crate::packets::car_telemetry::ButtonStatus;
+use crate::packets::header::PacketId;
+use crate::packets::motion::{GForce, RotationalAxes};
+use crate::packets::{
+    CarMotionData, CarSetupData, CarTelemetryData, Coordinates, EventData, EventDataDetails,
+    Header, LapData, MotionData, PacketCarSetupData, PacketCarStatusData, PacketCarTelemetryData,
+    PacketLapData, ParticipantData, ParticipantsData, SessionData,
+};
+use crate::{Telemetry, TelemetryData, WheelData};
+
+pub(crate) fn header(
+    packet_id: PacketId,
+    session_uid: u64,
+    session_time: f32,
+    frame: u32,
+) -> Header {
+    Header {
+        packet_format: 2019,
+        game_major_version: 1,
+        game_minor_version: 0,
+        packet_version: 1,
+        packet_id,
+        session_uid,
+        session_time,
+        frame_identifier: frame,
+        player_car_index: 0,
+    }
+}
+
+pub(crate) fn session_data(session_type: SessionType, track_id: TrackId) -> SessionData {
+    SessionData {
+        weather: Weather::Clear,
+        track_temperature: 30,
+        air_temperature: 20,
+        total_laps: 5,
+        track_length: 5000,
+        session_type,
+        track_id,
+        formula: Formula::Formula1Modern,
+        session_time_left: 3600,
+        session_duration: 3600,
+        pit_speed_limit: 80,
+        game_paused: 0,
+        is_spectating: 0,
+        spectator_car_index: 255,
+        sli_pro_native_support: 0,
+        num_marshal_zones: 0,
+        marshal_zones: Vec::new(),
+        safety_car_status: SafetyCarStatus::None,
+        network_game: NetworkGame::Offline,
+    }
+}
+
+pub(crate) fn session_packet(
+    session_uid: u64,
+    session_time: f32,
+    frame: u32,
+    data: SessionData,
+) -> Telemetry<'static> {
+    Telemetry {
+        header: header(PacketId::Session, session_uid, session_time, frame),
+        data: TelemetryData::Session(data),
+    }
+}
+
+pub(crate) fn event_packet(
+    session_uid: u64,
+    session_time: f32,
+    frame: u32,
+    event_string_code: &'static str,
+    event_details: EventDataDetails,
+) -> Telemetry<'static> {
+    Telemetry {
+        header: header(PacketId::Event, session_uid, session_time, frame),
+        data: TelemetryData::Event(EventData {
+            event_string_code,
+            event_details,
+        }),
+    }
+}
+
+pub(crate) fn lap_data(car_position: u8, current_lap_num: u8, total_distance: f32) -> LapData {
+    LapData {
+        last_lap_time: 0.0,
+        current_lap_time: 0.0,
+        best_lap_time: 0.0,
+        sector1_time: 0.0,
+        sector2_time: 0.0,
+        lap_distance: total_distance % 5000.0,
+        total_distance,
+        safety_car_delta: 0.0,
+        car_position,
+        current_lap_num,
+        pit_status: PitStatus::None,
+        sector: Sector::Sector1,
+        current_lap_invalid: LapState::Valid,
+        penalties: 0,
+        grid_position: car_position,
+        driver_status: DriverStatus::OnTrack,
+        result_status: ResultStatus::Active,
+    }
+}
+
+pub(crate) fn lap_packet(
+    session_uid: u64,
+    session_time: f32,
+    frame: u32,
+    lap_data: Vec<LapData>,
+) -> Telemetry<'static> {
+    Telemetry {
+        header: header(PacketId::LapData, session_uid, session_time, frame),
+        data: TelemetryData::Lap(PacketLapData { lap_data }),
+    }
+}
+
+pub(crate) fn participant(
+    name: &'static str,
+    team_id: TeamId,
+    race_number: u8,
+) -> ParticipantData<'static> {
+    ParticipantData {
+        ai_controlled: VehicleController::AI,
+        driver_id: DriverId::Unknown(255),
+        team_id,
+        race_number,
+        nationality: Nationality::British,
+        name,
+        your_telemetry: 1,
+    }
+}
+
+pub(crate) fn participants_packet(
+    session_uid: u64,
+    participants: Vec<ParticipantData<'static>>,
+) -> Telemetry<'static> {
+    Telemetry {
+        header: header(PacketId::Participants, session_uid, 0.0, 0),
+        data: TelemetryData::Participants(ParticipantsData {
+            number_active_cars: participants.len() as u8,
+            participants,
+        }),
+    }
+}
+
+pub(crate) fn wheels<T: Copy>(value: T) -> WheelData<T> {
+    WheelData {
+        rear_left: value,
+        rear_right: value,
+        front_left: value,
+        front_right: value,
+    }
+}
+
+pub(crate) fn car_telemetry(speed: u16, throttle: f32, brake: f32) -> CarTelemetryData {
+    CarTelemetryData {
+        speed,
+        throttle,
+        steer: 0.0,
+        brake,
+        clutch: 0,
+        gear: 4,
+        engine_rpm: 10000,
+        drs: 0,
+        rev_lights_percentage: 50,
+        brakes_temperature: wheels(500),
+        tyres_surface_temperature: wheels(95),
+        tyres_inner_temperature: wheels(100),
+        engine_temperature: 110,
+        tyres_pressure: wheels(22.0),
+        surface_type: wheels(SurfaceType::Tarmac),
+    }
+}
+
+pub(crate) fn car_telemetry_packet(
+    session_uid: u64,
+    session_time: f32,
+    frame: u32,
+    car_telemetry_data: Vec<CarTelemetryData>,
+) -> Telemetry<'static> {
+    Telemetry {
+        header: header(PacketId::CarTelemetry, session_uid, session_time, frame),
+        data: TelemetryData::CarTelemetry(PacketCarTelemetryData {
+            car_telemetry_data,
+            button_status: ButtonStatus::empty(),
+        }),
+    }
+}
+
+/// The compound with the game's id `id`, which must be a valid one.
+pub(crate) fn compound(id: u8) -> TyreCompound {
+    TyreCompound::try_from(id).ok().unwrap()
+}
+
+pub(crate) fn car_status(actual_tyre_compound: TyreCompound) -> CarStatusData {
+    CarStatusData {
+        traction_control: TractionControl::Off,
+        anti_lock_brakes: AntiLockBrakes::Off,
+        fuel_mix: FuelMix::Standard,
+        front_brake_bias: 56,
+        pit_limiter_status: 0,
+        fuel_in_tank: 50.0,
+        fuel_capacity: 110.0,
+        fuel_remaining_laps: 20.0,
+        max_rpm: 12000,
+        idle_rpm: 4000,
+        max_gears: 8,
+        drs_allowed: DrsAllowed::NotAllowed,
+        tyres_wear: wheels(0),
+        actual_tyre_compound,
+        tyre_visual_compound: VisualCompound::try_from(16).ok().unwrap(),
+        tyres_damage: wheels(0),
+        front_left_wing_damage: 0,
+        front_right_wing_damage: 0,
+        rear_wing_damage: 0,
+        engine_damage: 0,
+        gear_box_damage: 0,
+        vehicle_fia_flags: FiaFlag::None,
+        ers_store_energy: 4_000_000.0,
+        ers_deploy_mode: ErsDeployMode::Medium,
+        ers_harvested_this_lap_mguk: 0.0,
+        ers_harvested_this_lap_mguh: 0.0,
+        ers_deployed_this_lap: 0.0,
+    }
+}
+
+pub(crate) fn car_status_packet(
+    session_uid: u64,
+    session_time: f32,
+    frame: u32,
+    car_status_data: Vec<CarStatusData>,
+) -> Telemetry<'static> {
+    Telemetry {
+        header: header(PacketId::CarStatus, session_uid, session_time, frame),
+        data: TelemetryData::CarStatus(PacketCarStatusData { car_status_data }),
+    }
+}
+
+pub(crate) fn coordinates<T: Copy>(x: T, y: T, z: T) -> Coordinates<T> {
+    Coordinates { x, y, z }
+}
+
+pub(crate) fn car_motion(x: f32, z: f32) -> CarMotionData {
+    CarMotionData {
+        world_position: coordinates(x, 0.0, z),
+        world_velocity: coordinates(0.0, 0.0, 0.0),
+        world_forward_dir: coordinates(0, 0, 32767),
+        world_right_dir: coordinates(-32767, 0, 0),
+        g_force: GForce {
+            lateral: 0.0,
+            longitudinal: 0.0,
+            vertical: 1.0,
+        },
+        rotation: RotationalAxes {
+            yaw: 0.0,
+            pitch: 0.0,
+            roll: 0.0,
+        },
+    }
+}
+
+pub(crate) fn motion_data(car_motion_data: Vec<CarMotionData>) -> MotionData {
+    MotionData {
+        car_motion_data,
+        suspension_position: wheels(0.0),
+        suspension_velocity: wheels(0.0),
+        suspension_acceleration: wheels(0.0),
+        wheel_speed: wheels(0.0),
+        wheel_slip: wheels(0.0),
+        local_velocity: coordinates(0.0, 0.0, 0.0),
+        angular_velocity: coordinates(0.0, 0.0, 0.0),
+        angular_acceleration: coordinates(0.0, 0.0, 0.0),
+        front_wheels_angle: 0.0,
+    }
+}
+
+pub(crate) fn motion_packet(
+    session_uid: u64,
+    session_time: f32,
+    frame: u32,
+    data: MotionData,
+) -> Telemetry<'static> {
+    Telemetry {
+        header: header(PacketId::Motion, session_uid, session_time, frame),
+        data: TelemetryData::Motion(data),
+    }
+}
+
+pub(crate) fn car_setup() -> CarSetupData {
+    CarSetupData {
+        front_wing: 5,
+        rear_wing: 6,
+        on_throttle: 75,
+        off_throttle: 60,
+        front_camber: -3.0,
+        rear_camber: -1.5,
+        front_toe: 0.05,
+        rear_toe: 0.2,
+        front_suspension: 4,
+        rear_suspension: 3,
+        front_anti_roll_bar: 5,
+        rear_anti_roll_bar: 4,
+        front_suspension_height: 3,
+        rear_suspension_height: 6,
+        brake_pressure: 95,
+        brake_bias: 56,
+        front_tyre_pressure: 23.0,
+        rear_tyre_pressure: 20.5,
+        ballast: 6,
+        fuel_load: 10.0,
+    }
+}
+
+pub(crate) fn car_setups_packet(
+    session_uid: u64,
+    car_setups: Vec<CarSetupData>,
+) -> Telemetry<'static> {
+    Telemetry {
+        header: header(PacketId::CarSetups, session_uid, 0.0, 0),
+        data: TelemetryData::CarSetups(PacketCarSetupData { car_setups }),
+    }
+}
@@ -0,0 +1,513 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{BufRead, Write};
+
+use crate::analysis::setups::SETUP_FIELDS;
+use crate::error::LoadError;
+use crate::mappings::{LapState, SessionType, TrackId};
+use crate::packets::{CarMotionData, CarSetupData, CarTelemetryData, Coordinates, LapData};
+use crate::{Telemetry, TelemetryData};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TraceSample {
+    pub lap_distance: f32,
+    pub lap_time: f32,
+    pub speed: u16,
+    pub throttle: f32,
+    pub brake: f32,
+    pub steer: f32,
+    pub gear: i8,
+    pub world_position: Coordinates<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedLap {
+    pub track_id: TrackId,
+    pub lap_time: f32,
+    pub setup: Option<CarSetupData>,
+    pub samples: Vec<TraceSample>,
+}
+
+impl RecordedLap {
+    /// Adds a sample past the end of the trace. Samples behind the line,
+    /// with a negative lap distance, or not further along than the last one
+    /// are dropped so the trace stays ordered by distance.
+    pub fn push_sample(&mut self, sample: TraceSample) -> bool {
+        let ahead = match self.samples.last() {
+            Some(last) => sample.lap_distance > last.lap_distance,
+            None => sample.lap_distance >= 0.0,
+        };
+        if ahead {
+            self.samples.push(sample);
+        }
+        ahead
+    }
+
+    /// Lap time at `lap_distance`, interpolated between samples.
+    pub fn time_at(&self, lap_distance: f32) -> Option<f32> {
+        let after = self
+            .samples
+            .iter()
+            .position(|sample| sample.lap_distance >= lap_distance)?;
+        let next = &self.samples[after];
+        if after == 0 || next.lap_distance == lap_distance {
+            return Some(next.lap_time);
+        }
+
+        let previous = &self.samples[after - 1];
+        let ratio =
+            (lap_distance - previous.lap_distance) / (next.lap_distance - previous.lap_distance);
+        Some(previous.lap_time + (next.lap_time - previous.lap_time) * ratio)
+    }
+
+    /// Time gained (negative) or lost (positive) against `reference` at each
+    /// sample of this lap.
+    pub fn delta_to(&self, reference: &RecordedLap) -> Vec<(f32, f32)> {
+        self.samples
+            .iter()
+            .filter_map(|sample| {
+                reference
+                    .time_at(sample.lap_distance)
+                    .map(|time| (sample.lap_distance, sample.lap_time - time))
+            })
+            .collect()
+    }
+}
+
+/// Personal best laps, one per track.
+#[derive(Debug, Default)]
+pub struct LapArchive {
+    laps: HashMap<TrackId, RecordedLap>,
+}
+
+impl LapArchive {
+    pub fn new() -> Self {
+        LapArchive::default()
+    }
+
+    pub fn best(&self, track_id: TrackId) -> Option<&RecordedLap> {
+        self.laps.get(&track_id)
+    }
+
+    pub fn tracks(&self) -> impl Iterator<Item = &TrackId> {
+        self.laps.keys()
+    }
+
+    /// Stores the lap if it beats the current best, returning whether it did.
+    pub fn submit(&mut self, lap: RecordedLap) -> bool {
+        match self.laps.get(&lap.track_id) {
+            Some(best) if best.lap_time <= lap.lap_time => false,
+            _ => {
+                self.laps.insert(lap.track_id, lap);
+                true
+            }
+        }
+    }
+
+    /// Writes the archive as tab separated text.
+    ///
+    /// Each lap starts with a `lap` line holding the track id and lap time,
+    /// optionally followed by a `setup` line and then one `sample` line per
+    /// trace sample.
+    pub fn save<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let mut laps: Vec<&RecordedLap> = self.laps.values().collect();
+        laps.sort_by_key(|lap| lap.track_id as i8);
+
+        for lap in laps {
+            writeln!(writer, "lap\t{}\t{}", lap.track_id as i8, lap.lap_time)?;
+            if let Some(ref setup) = lap.setup {
+                let values: Vec<String> = setup_values(setup)
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect();
+                writeln!(writer, "setup\t{}", values.join("\t"))?;
+            }
+            for sample in &lap.samples {
+                writeln!(
+                    writer,
+                    "sample\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    sample.lap_distance,
+                    sample.lap_time,
+                    sample.speed,
+                    sample.throttle,
+                    sample.brake,
+                    sample.steer,
+                    sample.gear,
+                    sample.world_position.x,
+                    sample.world_position.y,
+                    sample.world_position.z
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load<R: BufRead>(reader: R) -> Result<Self, LoadError> {
+        let mut laps: Vec<RecordedLap> = Vec::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let malformed = || LoadError::malformed(index + 1);
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields[0] {
+                "lap" if fields.len() == 3 => {
+                    let track_id = fields[1]
+                        .parse::<i8>()
+                        .ok()
+                        .and_then(|id| TrackId::try_from(id).ok())
+                        .ok_or_else(malformed)?;
+                    laps.push(RecordedLap {
+                        track_id,
+                        lap_time: fields[2].parse().map_err(|_| malformed())?,
+                        setup: None,
+                        samples: Vec::new(),
+                    });
+                }
+                "setup" => {
+                    let values: Vec<f32> = fields[1..]
+                        .iter()
+                        .map(|value| value.parse())
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| malformed())?;
+                    let setup = setup_from_values(&values).ok_or_else(malformed)?;
+                    laps.last_mut().ok_or_else(malformed)?.setup = Some(setup);
+                }
+                "sample" if fields.len() == 11 => {
+                    let sample = parse_sample(&fields[1..]).ok_or_else(malformed)?;
+                    laps.last_mut().ok_or_else(malformed)?.push_sample(sample);
+                }
+                _ => return Err(malformed()),
+            }
+        }
+
+        let mut archive = LapArchive::new();
+        for lap in laps {
+            archive.submit(lap);
+        }
+        Ok(archive)
+    }
+}
+
+fn parse_sample(fields: &[&str]) -> Option<TraceSample> {
+    Some(TraceSample {
+        lap_distance: fields[0].parse().ok()?,
+        lap_time: fields[1].parse().ok()?,
+        speed: fields[2].parse().ok()?,
+        throttle: fields[3].parse().ok()?,
+        brake: fields[4].parse().ok()?,
+        steer: fields[5].parse().ok()?,
+        gear: fields[6].parse().ok()?,
+        world_position: Coordinates {
+            x: fields[7].parse().ok()?,
+            y: fields[8].parse().ok()?,
+            z: fields[9].parse().ok()?,
+        },
+    })
+}
+
+fn setup_values(setup: &CarSetupData) -> Vec<f32> {
+    SETUP_FIELDS.iter().map(|field| field.get(setup)).collect()
+}
+
+fn setup_from_values(values: &[f32]) -> Option<CarSetupData> {
+    if values.len() != SETUP_FIELDS.len() {
+        return None;
+    }
+
+    let mut setup = CarSetupData::default();
+    for (field, value) in SETUP_FIELDS.iter().zip(values) {
+        field.set(&mut setup, *value).ok()?;
+    }
+    Some(setup)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CompletedLap {
+    pub track_id: TrackId,
+    pub lap_time: f32,
+    pub personal_best: bool,
+    /// Difference to the previous personal best, negative when faster.
+    pub delta_to_best: Option<f32>,
+}
+
+/// Records the player's valid time trial laps into a `LapArchive`.
+///
+/// Recording starts at the first lap change seen, so a lap joined part way
+/// through is never stored.
+#[derive(Debug, Default)]
+pub struct TimeTrialRecorder {
+    archive: LapArchive,
+    session_uid: u64,
+    track_id: Option<TrackId>,
+    time_trial: bool,
+    telemetry: Option<CarTelemetryData>,
+    motion: Option<CarMotionData>,
+    setup: Option<CarSetupData>,
+    previous: Option<LapData>,
+    current: Option<RecordedLap>,
+}
+
+impl TimeTrialRecorder {
+    pub fn new(archive: LapArchive) -> Self {
+        TimeTrialRecorder {
+            archive,
+            ..TimeTrialRecorder::default()
+        }
+    }
+
+    pub fn archive(&self) -> &LapArchive {
+        &self.archive
+    }
+
+    pub fn into_archive(self) -> LapArchive {
+        self.archive
+    }
+
+    pub fn update(&mut self, packet: &Telemetry) -> Option<CompletedLap> {
+        let player = packet.header.player_car_index as usize;
+        if packet.header.session_uid != self.session_uid {
+            self.session_uid = packet.header.session_uid;
+            self.previous = None;
+            self.current = None;
+        }
+
+        match packet.data {
+            TelemetryData::Session(ref data) => {
+                self.time_trial = data.session_type == SessionType::TimeTrial;
+                if self.track_id.replace(data.track_id) != Some(data.track_id) {
+                    self.previous = None;
+                    self.current = None;
+                }
+            }
+            TelemetryData::CarTelemetry(ref data) => {
+                self.telemetry = data.car_telemetry_data.get(player).copied()
+            }
+            TelemetryData::Motion(ref data) => {
+                self.motion = data.car_motion_data.get(player).copied()
+            }
+            TelemetryData::CarSetups(ref data) => self.setup = data.car_setups.get(player).copied(),
+            TelemetryData::Lap(ref data) => {
+                if let Some(lap) = data.lap_data.get(player) {
+                    return self.update_lap(lap);
+                }
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    fn update_lap(&mut self, lap: &LapData) -> Option<CompletedLap> {
+        let track_id = match self.track_id {
+            Some(track_id) if self.time_trial => track_id,
+            _ => return None,
+        };
+        let previous = self.previous.replace(*lap)?;
+
+        let mut completed = None;
+        if previous.current_lap_num != lap.current_lap_num {
+            let recorded = self.current.replace(RecordedLap {
+                track_id,
+                lap_time: 0.0,
+                setup: None,
+                samples: Vec::new(),
+            });
+            if let Some(mut recorded) = recorded {
+                if lap.current_lap_num > previous.current_lap_num
+                    && previous.current_lap_invalid == LapState::Valid
+                    && lap.last_lap_time > 0.0
+                {
+                    let best = self.archive.best(track_id).map(|best| best.lap_time);
+                    recorded.lap_time = lap.last_lap_time;
+                    recorded.setup = self.setup;
+                    let personal_best = self.archive.submit(recorded);
+                    completed = Some(CompletedLap {
+                        track_id,
+                        lap_time: lap.last_lap_time,
+                        personal_best,
+                        delta_to_best: best.map(|best| lap.last_lap_time - best),
+                    });
+                }
+            }
+        }
+
+        if let (Some(current), Some(telemetry), Some(motion)) =
+            (self.current.as_mut(), self.telemetry, self.motion)
+        {
+            current.push_sample(TraceSample {
+                lap_distance: lap.lap_distance,
+                lap_time: lap.current_lap_time,
+                speed: telemetry.speed,
+                throttle: telemetry.throttle,
+                brake: telemetry.brake,
+                steer: telemetry.steer,
+                gear: telemetry.gear,
+                world_position: motion.world_position,
+            });
+        }
+
+        completed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{
+        car_motion, car_setup, car_telemetry, car_telemetry_packet, lap_data, lap_packet,
+        motion_data, motion_packet, session_data, session_packet,
+    };
+
+    fn sample(lap_distance: f32, lap_time: f32) -> TraceSample {
+        TraceSample {
+            lap_distance,
+            lap_time,
+            speed: 200,
+            throttle: 1.0,
+            brake: 0.0,
+            steer: 0.0,
+            gear: 6,
+            world_position: Coordinates {
+                x: lap_distance,
+                y: 0.0,
+                z: 0.5,
+            },
+        }
+    }
+
+    fn recorded(lap_time: f32, samples: Vec<TraceSample>) -> RecordedLap {
+        RecordedLap {
+            track_id: TrackId::Monaco,
+            lap_time,
+            setup: Some(car_setup()),
+            samples,
+        }
+    }
+
+    #[test]
+    fn test_delta_to_reference() {
+        let reference = recorded(70.0, vec![sample(0.0, 0.0), sample(100.0, 2.0)]);
+        let attempt = recorded(69.0, vec![sample(50.0, 0.9), sample(100.0, 2.2)]);
+
+        assert_eq!(reference.time_at(50.0), Some(1.0));
+        let deltas = attempt.delta_to(&reference);
+        assert_eq!(deltas.len(), 2);
+        assert!((deltas[0].1 + 0.1).abs() < 1e-5);
+        assert!((deltas[1].1 - 0.2).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_archive_keeps_best_and_round_trips() {
+        let mut archive = LapArchive::new();
+        assert!(archive.submit(recorded(71.0, vec![sample(0.0, 0.0)])));
+        assert!(archive.submit(recorded(70.5, vec![sample(0.0, 0.0), sample(10.0, 0.4)])));
+        assert!(!archive.submit(recorded(70.9, Vec::new())));
+
+        let mut saved = Vec::new();
+        archive.save(&mut saved).unwrap();
+        let loaded = LapArchive::load(&saved[..]).unwrap();
+        assert_eq!(loaded.best(TrackId::Monaco), archive.best(TrackId::Monaco));
+        assert_eq!(loaded.best(TrackId::Monaco).unwrap().lap_time, 70.5);
+    }
+
+    fn recorder() -> TimeTrialRecorder {
+        let mut recorder = TimeTrialRecorder::new(LapArchive::new());
+        recorder.update(&session_packet(
+            1,
+            0.0,
+            0,
+            session_data(SessionType::TimeTrial, TrackId::Monaco),
+        ));
+        recorder.update(&car_telemetry_packet(
+            1,
+            0.0,
+            0,
+            vec![car_telemetry(250, 1.0, 0.0)],
+        ));
+        recorder.update(&motion_packet(
+            1,
+            0.0,
+            0,
+            motion_data(vec![car_motion(1.0, 2.0)]),
+        ));
+        recorder
+    }
+
+    fn lap(
+        recorder: &mut TimeTrialRecorder,
+        lap_num: u8,
+        distance: f32,
+        last_lap_time: f32,
+    ) -> Option<CompletedLap> {
+        let mut data = lap_data(1, lap_num, distance);
+        data.last_lap_time = last_lap_time;
+        recorder.update(&lap_packet(1, 0.0, 0, vec![data]))
+    }
+
+    #[test]
+    fn test_records_personal_best() {
+        let mut recorder = recorder();
+        let mut lap =
+            |lap_num, distance, last_lap_time| lap(&mut recorder, lap_num, distance, last_lap_time);
+        assert_eq!(lap(1, 4900.0, 0.0), None);
+        assert_eq!(lap(2, 3.0, 0.0), None);
+        assert_eq!(lap(2, 3300.0, 0.0), None);
+        assert_eq!(
+            lap(3, 2.0, 72.5),
+            Some(CompletedLap {
+                track_id: TrackId::Monaco,
+                lap_time: 72.5,
+                personal_best: true,
+                delta_to_best: None,
+            })
+        );
+        lap(3, 1000.0, 72.5);
+        let slower = lap(4, 2.0, 73.0).unwrap();
+        assert!(!slower.personal_best);
+        assert_eq!(slower.delta_to_best, Some(0.5));
+
+        let best = recorder.archive().best(TrackId::Monaco).unwrap();
+        assert_eq!(best.lap_time, 72.5);
+        assert_eq!(best.samples.len(), 2);
+        assert_eq!(best.samples[0].lap_distance, 3.0);
+    }
+
+    #[test]
+    fn test_lap_joined_part_way_is_not_recorded() {
+        let mut recorder = recorder();
+        assert_eq!(lap(&mut recorder, 1, 2500.0, 0.0), None);
+        assert_eq!(lap(&mut recorder, 2, 2.0, 72.0), None);
+        lap(&mut recorder, 2, 1500.0, 72.0);
+
+        // A new session starts part way round a lap as well.
+        recorder.update(&session_packet(
+            2,
+            0.0,
+            0,
+            session_data(SessionType::TimeTrial, TrackId::Monaco),
+        ));
+        let mut data = lap_data(1, 7, 3000.0);
+        recorder.update(&lap_packet(2, 0.0, 0, vec![data]));
+        data.current_lap_num = 8;
+        data.last_lap_time = 71.0;
+        assert_eq!(recorder.update(&lap_packet(2, 0.0, 0, vec![data])), None);
+        assert!(recorder.archive().best(TrackId::Monaco).is_none());
+    }
+
+    #[test]
+    fn test_samples_stay_ordered() {
+        let mut lap = recorded(70.0, Vec::new());
+        assert!(!lap.push_sample(sample(-20.0, 0.0)));
+        assert!(lap.push_sample(sample(0.5, 0.1)));
+        assert!(lap.push_sample(sample(100.0, 2.0)));
+        assert!(!lap.push_sample(sample(60.0, 2.1)));
+        assert!(lap.push_sample(sample(200.0, 4.0)));
+
+        assert_eq!(lap.samples.len(), 3);
+        assert_eq!(lap.time_at(150.0), Some(3.0));
+    }
+}
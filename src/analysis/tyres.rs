@@ -0,0 +1,339 @@
+use crate::analysis::stats::Summary;
+use crate::mappings::{Sector, TyreCompound};
+use crate::packets::CarTelemetryData;
+use crate::{Telemetry, TelemetryData, Wheel, WheelData};
+
+/// Gaps between telemetry packets longer than this (in seconds), e.g. while
+/// paused, are not counted towards time in the temperature window.
+const MAX_SAMPLE_INTERVAL: f32 = 1.0;
+
+/// Surface temperature range, in degrees celsius, a compound works best in.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TemperatureWindow {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl TemperatureWindow {
+    pub fn contains(&self, temperature: f32) -> bool {
+        temperature >= self.min && temperature <= self.max
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TyreWindows {
+    pub dry: TemperatureWindow,
+    pub wet: TemperatureWindow,
+    overrides: Vec<(TyreCompound, TemperatureWindow)>,
+}
+
+impl TyreWindows {
+    pub fn new(dry: TemperatureWindow, wet: TemperatureWindow) -> Self {
+        TyreWindows {
+            dry,
+            wet,
+            overrides: Vec::new(),
+        }
+    }
+
+    /// Uses a specific window for one compound instead of the dry or wet
+    /// default.
+    pub fn with_compound(mut self, compound: TyreCompound, window: TemperatureWindow) -> Self {
+        self.overrides.retain(|(existing, _)| *existing != compound);
+        self.overrides.push((compound, window));
+        self
+    }
+
+    pub fn window(&self, compound: TyreCompound) -> TemperatureWindow {
+        match self
+            .overrides
+            .iter()
+            .find(|(existing, _)| *existing == compound)
+        {
+            Some((_, window)) => *window,
+            None if compound.is_wet() => self.wet,
+            None => self.dry,
+        }
+    }
+}
+
+impl Default for TyreWindows {
+    fn default() -> Self {
+        TyreWindows::new(
+            TemperatureWindow {
+                min: 90.0,
+                max: 110.0,
+            },
+            TemperatureWindow {
+                min: 60.0,
+                max: 85.0,
+            },
+        )
+    }
+}
+
+/// Difference in mean surface temperature between the front and rear, and
+/// the left and right, tyres.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Imbalance {
+    pub front_rear: f32,
+    pub left_right: f32,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct TyreStats {
+    pub surface_temperature: WheelData<Summary>,
+    pub inner_temperature: WheelData<Summary>,
+    pub pressure: WheelData<Summary>,
+    pub time_in_window: WheelData<f32>,
+    pub time: f32,
+}
+
+impl TyreStats {
+    fn add(&mut self, telemetry: &CarTelemetryData, window: Option<TemperatureWindow>, dt: f32) {
+        self.time += dt;
+        for &wheel in Wheel::ALL.iter() {
+            let surface = *telemetry.tyres_surface_temperature.get(wheel) as f32;
+            self.surface_temperature.get_mut(wheel).add(surface);
+            self.inner_temperature
+                .get_mut(wheel)
+                .add(*telemetry.tyres_inner_temperature.get(wheel) as f32);
+            self.pressure
+                .get_mut(wheel)
+                .add(*telemetry.tyres_pressure.get(wheel));
+            if matches!(window, Some(window) if window.contains(surface)) {
+                *self.time_in_window.get_mut(wheel) += dt;
+            }
+        }
+    }
+
+    /// Share of the time the tyre spent inside its temperature window.
+    pub fn window_ratio(&self, wheel: Wheel) -> Option<f32> {
+        if self.time > 0.0 {
+            Some(self.time_in_window.get(wheel) / self.time)
+        } else {
+            None
+        }
+    }
+
+    pub fn imbalance(&self) -> Option<Imbalance> {
+        let mean = |wheels: &[Wheel]| -> Option<f32> {
+            let mut total = 0.0;
+            for wheel in wheels {
+                total += self.surface_temperature.get(*wheel).mean()?;
+            }
+            Some(total / wheels.len() as f32)
+        };
+
+        Some(Imbalance {
+            front_rear: mean(&[Wheel::FrontLeft, Wheel::FrontRight])?
+                - mean(&[Wheel::RearLeft, Wheel::RearRight])?,
+            left_right: mean(&[Wheel::FrontLeft, Wheel::RearLeft])?
+                - mean(&[Wheel::FrontRight, Wheel::RearRight])?,
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LapTyres {
+    pub lap: u8,
+    pub compound: Option<TyreCompound>,
+    pub overall: TyreStats,
+    pub sectors: [TyreStats; 3],
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OverheatAlert {
+    pub vehicle_index: u8,
+    pub wheel: Wheel,
+    pub lap: u8,
+    pub session_time: f32,
+    pub temperature: u16,
+    pub limit: f32,
+}
+
+#[derive(Debug, Default)]
+struct CarTyres {
+    lap: u8,
+    sector: usize,
+    compound: Option<TyreCompound>,
+    overheating: WheelData<bool>,
+    laps: Vec<LapTyres>,
+}
+
+impl CarTyres {
+    fn current_lap(&mut self) -> &mut LapTyres {
+        let lap = self.lap;
+        if self.laps.last().map(|last| last.lap) != Some(lap) {
+            self.laps.push(LapTyres {
+                lap,
+                compound: self.compound,
+                overall: TyreStats::default(),
+                sectors: [TyreStats::default(); 3],
+            });
+        }
+        self.laps.last_mut().unwrap()
+    }
+}
+
+/// Tyre temperature and pressure statistics per car, lap and sector.
+#[derive(Debug, Default)]
+pub struct TyreAnalyzer {
+    windows: TyreWindows,
+    last_time: Option<f32>,
+    cars: Vec<CarTyres>,
+}
+
+impl TyreAnalyzer {
+    pub fn new(windows: TyreWindows) -> Self {
+        TyreAnalyzer {
+            windows,
+            ..TyreAnalyzer::default()
+        }
+    }
+
+    pub fn laps(&self, vehicle_index: u8) -> &[LapTyres] {
+        self.cars
+            .get(vehicle_index as usize)
+            .map_or(&[], |car| &car.laps[..])
+    }
+
+    fn car(&mut self, vehicle_index: usize) -> &mut CarTyres {
+        if self.cars.len() <= vehicle_index {
+            self.cars.resize_with(vehicle_index + 1, CarTyres::default);
+        }
+        &mut self.cars[vehicle_index]
+    }
+
+    pub fn update(&mut self, packet: &Telemetry) -> Vec<OverheatAlert> {
+        let session_time = packet.header.session_time;
+        let mut alerts = Vec::new();
+
+        match packet.data {
+            TelemetryData::Lap(ref data) => {
+                for (index, lap) in data.lap_data.iter().enumerate() {
+                    let car = self.car(index);
+                    car.lap = lap.current_lap_num;
+                    car.sector = match lap.sector {
+                        Sector::Sector1 => 0,
+                        Sector::Sector2 => 1,
+                        Sector::Sector3 => 2,
+                    };
+                }
+            }
+            TelemetryData::CarStatus(ref data) => {
+                for (index, status) in data.car_status_data.iter().enumerate() {
+                    self.car(index).compound = Some(status.actual_tyre_compound);
+                }
+            }
+            TelemetryData::CarTelemetry(ref data) => {
+                let dt = match self.last_time.replace(session_time) {
+                    Some(last) if session_time > last => {
+                        (session_time - last).min(MAX_SAMPLE_INTERVAL)
+                    }
+                    _ => 0.0,
+                };
+
+                for (index, telemetry) in data.car_telemetry_data.iter().enumerate() {
+                    let windows = &self.windows;
+                    let window = self
+                        .cars
+                        .get(index)
+                        .and_then(|car| car.compound)
+                        .map(|compound| windows.window(compound));
+                    let car = self.car(index);
+                    let sector = car.sector;
+                    let lap = car.current_lap();
+                    lap.overall.add(telemetry, window, dt);
+                    lap.sectors[sector].add(telemetry, window, dt);
+
+                    let window = match window {
+                        Some(window) => window,
+                        None => continue,
+                    };
+                    for &wheel in Wheel::ALL.iter() {
+                        let temperature = *telemetry.tyres_surface_temperature.get(wheel);
+                        let overheating = temperature as f32 > window.max;
+                        let was_overheating =
+                            std::mem::replace(car.overheating.get_mut(wheel), overheating);
+                        if overheating && !was_overheating {
+                            alerts.push(OverheatAlert {
+                                vehicle_index: index as u8,
+                                wheel,
+                                lap: car.lap,
+                                session_time,
+                                temperature,
+                                limit: window.max,
+                            });
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{
+        car_status, car_status_packet, car_telemetry, car_telemetry_packet, compound, lap_data,
+        lap_packet,
+    };
+
+    #[test]
+    fn test_window_lookup() {
+        let windows = TyreWindows::default();
+        let soft = compound(16);
+        let wet = compound(8);
+        assert_eq!(windows.window(soft).max, 110.0);
+        assert_eq!(windows.window(wet).max, 85.0);
+
+        let hot = TemperatureWindow {
+            min: 95.0,
+            max: 120.0,
+        };
+        assert_eq!(windows.with_compound(soft, hot).window(soft), hot);
+    }
+
+    #[test]
+    fn test_lap_statistics_and_alerts() {
+        let mut analyzer = TyreAnalyzer::default();
+        let soft = compound(16);
+        analyzer.update(&car_status_packet(1, 0.0, 0, vec![car_status(soft)]));
+        analyzer.update(&lap_packet(1, 0.0, 0, vec![lap_data(1, 3, 0.0)]));
+
+        let mut telemetry = car_telemetry(200, 1.0, 0.0);
+        telemetry.tyres_surface_temperature.front_left = 100;
+        telemetry.tyres_surface_temperature.front_right = 100;
+        analyzer.update(&car_telemetry_packet(1, 1.0, 1, vec![telemetry]));
+
+        telemetry.tyres_surface_temperature.front_left = 115;
+        telemetry.tyres_pressure.front_left = 23.0;
+        let alerts = analyzer.update(&car_telemetry_packet(1, 1.5, 2, vec![telemetry]));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].wheel, Wheel::FrontLeft);
+        assert_eq!(alerts[0].temperature, 115);
+
+        let alerts = analyzer.update(&car_telemetry_packet(1, 2.0, 3, vec![telemetry]));
+        assert!(alerts.is_empty());
+
+        let laps = analyzer.laps(0);
+        assert_eq!(laps.len(), 1);
+        let stats = &laps[0].overall;
+        assert_eq!(stats.surface_temperature.front_left.max, 115.0);
+        assert_eq!(stats.pressure.front_left.max, 23.0);
+        assert_eq!(stats.time, 1.0);
+        assert_eq!(stats.window_ratio(Wheel::FrontRight), Some(1.0));
+        assert_eq!(stats.window_ratio(Wheel::FrontLeft), Some(0.0));
+        assert_eq!(stats.window_ratio(Wheel::RearLeft), Some(1.0));
+
+        let imbalance = stats.imbalance().unwrap();
+        assert!((imbalance.front_rear - (105.0 - 95.0)).abs() < 1e-3);
+        assert!((imbalance.left_right - (110.0 + 95.0 - 100.0 - 95.0) / 2.0).abs() < 1e-3);
+        assert_eq!(laps[0].sectors[0], *stats);
+    }
+}
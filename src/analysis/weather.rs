@@ -0,0 +1,250 @@
+use crate::analysis::stats::{pearson, Summary};
+use crate::mappings::Weather;
+use crate::{Telemetry, TelemetryData};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Conditions {
+    pub session_time: f32,
+    pub weather: Weather,
+    pub track_temperature: i8,
+    pub air_temperature: i8,
+}
+
+/// The tyres a change in weather calls for.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TyreCall {
+    Slicks,
+    Intermediates,
+    Wets,
+}
+
+impl TyreCall {
+    fn for_weather(weather: Weather) -> Self {
+        match weather {
+            Weather::Clear | Weather::LightCloud | Weather::Overcast => TyreCall::Slicks,
+            Weather::LightRain => TyreCall::Intermediates,
+            Weather::HeavyRain | Weather::Storm => TyreCall::Wets,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WeatherTransition {
+    pub session_time: f32,
+    pub from: Weather,
+    pub to: Weather,
+    /// Set when the new weather needs a different kind of tyre.
+    pub tyre_call: Option<TyreCall>,
+}
+
+/// Conditions during one of the player's laps.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LapConditions {
+    pub lap: u8,
+    pub lap_time: f32,
+    pub track_temperature: Summary,
+    pub air_temperature: Summary,
+    /// Mean surface temperature of all four tyres.
+    pub tyre_temperature: Summary,
+}
+
+/// Timeline of weather and temperatures, with the player's laps for
+/// comparing pace against the conditions.
+#[derive(Debug, Default)]
+pub struct WeatherTracker {
+    timeline: Vec<Conditions>,
+    lap: Option<u8>,
+    current: Option<LapConditions>,
+    laps: Vec<LapConditions>,
+}
+
+impl WeatherTracker {
+    pub fn new() -> Self {
+        WeatherTracker::default()
+    }
+
+    /// Every change of weather or temperature, starting with the first
+    /// conditions seen.
+    pub fn timeline(&self) -> &[Conditions] {
+        &self.timeline
+    }
+
+    pub fn laps(&self) -> &[LapConditions] {
+        &self.laps
+    }
+
+    /// Returns a transition when the weather changed with this packet.
+    pub fn update(&mut self, packet: &Telemetry) -> Option<WeatherTransition> {
+        let session_time = packet.header.session_time;
+        let player = packet.header.player_car_index as usize;
+
+        match packet.data {
+            TelemetryData::Session(ref data) => {
+                let conditions = Conditions {
+                    session_time,
+                    weather: data.weather,
+                    track_temperature: data.track_temperature,
+                    air_temperature: data.air_temperature,
+                };
+                if let Some(lap) = self.current.as_mut() {
+                    lap.track_temperature.add(data.track_temperature as f32);
+                    lap.air_temperature.add(data.air_temperature as f32);
+                }
+                return self.record(conditions);
+            }
+            TelemetryData::CarTelemetry(ref data) => {
+                if let (Some(lap), Some(telemetry)) =
+                    (self.current.as_mut(), data.car_telemetry_data.get(player))
+                {
+                    let temperatures = telemetry.tyres_surface_temperature.to_array();
+                    let mean = temperatures.iter().map(|&t| t as f32).sum::<f32>() / 4.0;
+                    lap.tyre_temperature.add(mean);
+                }
+            }
+            TelemetryData::Lap(ref data) => {
+                if let Some(lap) = data.lap_data.get(player) {
+                    self.update_lap(lap.current_lap_num, lap.last_lap_time);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn record(&mut self, conditions: Conditions) -> Option<WeatherTransition> {
+        let previous = match self.timeline.last() {
+            Some(previous) => *previous,
+            None => {
+                self.timeline.push(conditions);
+                return None;
+            }
+        };
+
+        let changed = previous.weather != conditions.weather
+            || previous.track_temperature != conditions.track_temperature
+            || previous.air_temperature != conditions.air_temperature;
+        if changed {
+            self.timeline.push(conditions);
+        }
+        if previous.weather == conditions.weather {
+            return None;
+        }
+
+        let from = TyreCall::for_weather(previous.weather);
+        let to = TyreCall::for_weather(conditions.weather);
+        Some(WeatherTransition {
+            session_time: conditions.session_time,
+            from: previous.weather,
+            to: conditions.weather,
+            tyre_call: if from == to { None } else { Some(to) },
+        })
+    }
+
+    fn update_lap(&mut self, lap: u8, last_lap_time: f32) {
+        if self.lap == Some(lap) {
+            return;
+        }
+
+        let completed = self.current.take();
+        if let Some(mut completed) = completed {
+            if self.lap == Some(lap.wrapping_sub(1)) {
+                completed.lap_time = last_lap_time;
+                self.laps.push(completed);
+            }
+        }
+
+        self.lap = Some(lap);
+        self.current = Some(LapConditions {
+            lap,
+            lap_time: 0.0,
+            track_temperature: Summary::default(),
+            air_temperature: Summary::default(),
+            tyre_temperature: Summary::default(),
+        });
+    }
+
+    /// Correlation between lap time and mean track temperature.
+    pub fn lap_time_correlation(&self) -> Option<f32> {
+        let points: Vec<(f32, f32)> = self
+            .laps
+            .iter()
+            .filter_map(|lap| Some((lap.track_temperature.mean()?, lap.lap_time)))
+            .collect();
+        pearson(&points)
+    }
+
+    /// Correlation between tyre surface temperature and track temperature.
+    pub fn tyre_temperature_correlation(&self) -> Option<f32> {
+        let points: Vec<(f32, f32)> = self
+            .laps
+            .iter()
+            .filter_map(|lap| Some((lap.track_temperature.mean()?, lap.tyre_temperature.mean()?)))
+            .collect();
+        pearson(&points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_support::{
+        car_telemetry, car_telemetry_packet, lap_data, lap_packet, session_data, session_packet,
+    };
+    use crate::mappings::{SessionType, TrackId};
+
+    fn session(time: f32, weather: Weather, track_temperature: i8) -> Telemetry<'static> {
+        let mut data = session_data(SessionType::Race, TrackId::Silverstone);
+        data.weather = weather;
+        data.track_temperature = track_temperature;
+        session_packet(1, time, 0, data)
+    }
+
+    #[test]
+    fn test_transitions() {
+        let mut tracker = WeatherTracker::new();
+        assert_eq!(tracker.update(&session(0.0, Weather::LightCloud, 30)), None);
+        assert_eq!(tracker.update(&session(1.0, Weather::LightCloud, 30)), None);
+        assert_eq!(tracker.update(&session(2.0, Weather::LightCloud, 29)), None);
+
+        let transition = tracker
+            .update(&session(3.0, Weather::Overcast, 29))
+            .unwrap();
+        assert_eq!(transition.tyre_call, None);
+        let transition = tracker
+            .update(&session(4.0, Weather::LightRain, 28))
+            .unwrap();
+        assert_eq!(transition.from, Weather::Overcast);
+        assert_eq!(transition.tyre_call, Some(TyreCall::Intermediates));
+
+        assert_eq!(tracker.timeline().len(), 4);
+        assert_eq!(tracker.timeline()[3].track_temperature, 28);
+    }
+
+    #[test]
+    fn test_lap_correlation() {
+        let mut tracker = WeatherTracker::new();
+        let mut time = 0.0;
+        for (lap, (track_temperature, lap_time)) in [(40, 0.0), (35, 80.0), (30, 81.0), (25, 82.5)]
+            .iter()
+            .enumerate()
+        {
+            let mut lap_data = lap_data(1, lap as u8 + 1, 0.0);
+            lap_data.last_lap_time = *lap_time;
+            tracker.update(&lap_packet(1, time, 0, vec![lap_data]));
+            tracker.update(&session(time, Weather::Clear, *track_temperature));
+
+            let mut telemetry = car_telemetry(200, 1.0, 0.0);
+            telemetry.tyres_surface_temperature.front_left = 60 + *track_temperature as u16;
+            tracker.update(&car_telemetry_packet(1, time, 0, vec![telemetry]));
+            time += 80.0;
+        }
+
+        // The last lap is still running, so the first three are complete.
+        let laps = tracker.laps();
+        assert_eq!(laps.len(), 3);
+        assert_eq!(laps[0].lap_time, 80.0);
+        assert_eq!(laps[0].track_temperature.mean(), Some(40.0));
+        assert!(tracker.lap_time_correlation().unwrap() < -0.9);
+        assert!((tracker.tyre_temperature_correlation().unwrap() - 1.0).abs() < 1e-4);
+    }
+}
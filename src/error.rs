@@ -37,3 +37,51 @@ impl fmt::Display for ParseError {
         }
     }
 }
+
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum LoadErrorKind {
+    Io(std::io::Error),
+    Malformed { line: usize },
+}
+
+#[derive(Debug)]
+pub struct LoadError(LoadErrorKind);
+
+impl LoadError {
+    pub(crate) fn new(kind: LoadErrorKind) -> LoadError {
+        LoadError(kind)
+    }
+
+    pub(crate) fn malformed(line: usize) -> LoadError {
+        LoadError::new(LoadErrorKind::Malformed { line })
+    }
+
+    pub fn kind(&self) -> &LoadErrorKind {
+        &self.0
+    }
+}
+
+impl From<std::io::Error> for LoadError {
+    fn from(error: std::io::Error) -> Self {
+        LoadError::new(LoadErrorKind::Io(error))
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self.0 {
+            LoadErrorKind::Io(ref error) => Some(error),
+            LoadErrorKind::Malformed { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            LoadErrorKind::Io(ref error) => write!(f, "Error reading data: {}", error),
+            LoadErrorKind::Malformed { line } => write!(f, "Malformed data on line {}", line),
+        }
+    }
+}
@@ -4,6 +4,7 @@ use nom::number::complete::{le_f32, le_u16, le_u8};
 use nom::sequence::tuple;
 use nom::IResult;
 
+pub mod analysis;
 pub mod error;
 pub mod mappings;
 mod packets;
@@ -20,7 +21,33 @@ pub const MAXIMUM_PACKET_SIZE: usize = 1347;
 
 type ParseResult<'a, O, E = (&'a [u8], ErrorKind)> = IResult<&'a [u8], O, E>;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Wheel {
+    RearLeft,
+    RearRight,
+    FrontLeft,
+    FrontRight,
+}
+
+impl Wheel {
+    /// Every wheel, in the same order as `WheelData::to_array`.
+    pub const ALL: [Wheel; 4] = [
+        Wheel::RearLeft,
+        Wheel::RearRight,
+        Wheel::FrontLeft,
+        Wheel::FrontRight,
+    ];
+
+    pub fn is_front(self) -> bool {
+        self == Wheel::FrontLeft || self == Wheel::FrontRight
+    }
+
+    pub fn is_left(self) -> bool {
+        self == Wheel::FrontLeft || self == Wheel::RearLeft
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
 pub struct WheelData<T> {
     pub rear_left: T,
     pub rear_right: T,
@@ -28,6 +55,48 @@ pub struct WheelData<T> {
     pub front_right: T,
 }
 
+impl<T: Copy> WheelData<T> {
+    /// Returns the values in `rear_left`, `rear_right`, `front_left`,
+    /// `front_right` order.
+    pub fn to_array(&self) -> [T; 4] {
+        [
+            self.rear_left,
+            self.rear_right,
+            self.front_left,
+            self.front_right,
+        ]
+    }
+}
+
+impl<T> WheelData<T> {
+    pub fn get(&self, wheel: Wheel) -> &T {
+        match wheel {
+            Wheel::RearLeft => &self.rear_left,
+            Wheel::RearRight => &self.rear_right,
+            Wheel::FrontLeft => &self.front_left,
+            Wheel::FrontRight => &self.front_right,
+        }
+    }
+
+    pub fn get_mut(&mut self, wheel: Wheel) -> &mut T {
+        match wheel {
+            Wheel::RearLeft => &mut self.rear_left,
+            Wheel::RearRight => &mut self.rear_right,
+            Wheel::FrontLeft => &mut self.front_left,
+            Wheel::FrontRight => &mut self.front_right,
+        }
+    }
+
+    pub fn map<U, F: FnMut(&T) -> U>(&self, mut f: F) -> WheelData<U> {
+        WheelData {
+            rear_left: f(&self.rear_left),
+            rear_right: f(&self.rear_right),
+            front_left: f(&self.front_left),
+            front_right: f(&self.front_right),
+        }
+    }
+}
+
 impl WheelData<f32> {
     fn parse_f32(input: &[u8]) -> ParseResult<WheelData<f32>> {
         map(
@@ -5,7 +5,7 @@ use nom::number::complete::le_i8;
 
 use crate::ParseResult;
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 #[repr(i8)]
 pub enum TrackId {
     Unknown = -1,
@@ -76,6 +76,17 @@ impl TryFrom<u8> for TyreCompound {
 }
 
 impl TyreCompound {
+    /// Whether this is an intermediate or full wet tyre.
+    pub fn is_wet(self) -> bool {
+        matches!(
+            self,
+            TyreCompound::F1Modern(F1Modern::Intermediate)
+                | TyreCompound::F1Modern(F1Modern::Wet)
+                | TyreCompound::F1Classic(F1Classic::Wet)
+                | TyreCompound::F2(F2::Wet)
+        )
+    }
+
     pub fn parse(input: &[u8]) -> ParseResult<Self> {
         map_res(le_u8, |tyre_compound: u8| {
             TyreCompound::try_from(tyre_compound)
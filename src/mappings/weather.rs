@@ -5,7 +5,7 @@ use nom::number::complete::le_u8;
 
 use crate::ParseResult;
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 #[repr(u8)]
 pub enum Weather {
     Clear = 0,
@@ -5,7 +5,7 @@ use nom::sequence::tuple;
 
 use crate::ParseResult;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
 pub struct CarSetupData {
     pub front_wing: u8,
     pub rear_wing: u8,
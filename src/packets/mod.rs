@@ -1,19 +1,19 @@
-pub use self::car_setups::PacketCarSetupData;
+pub use self::car_setups::{CarSetupData, PacketCarSetupData};
 pub use self::car_status::PacketCarStatusData;
-pub use self::car_telemetry::PacketCarTelemetryData;
-pub use self::event::EventData;
+pub use self::car_telemetry::{CarTelemetryData, PacketCarTelemetryData};
+pub use self::event::{EventData, EventDataDetails};
 pub use self::header::Header;
-pub use self::lap_data::PacketLapData;
-pub use self::motion::MotionData;
-pub use self::participants::ParticipantsData;
-pub use self::session::SessionData;
+pub use self::lap_data::{LapData, PacketLapData};
+pub use self::motion::{CarMotionData, Coordinates, MotionData};
+pub use self::participants::{ParticipantData, ParticipantsData};
+pub use self::session::{MarshalZone, SessionData};
 
 mod car_setups;
-mod car_status;
-mod car_telemetry;
+pub(crate) mod car_status;
+pub(crate) mod car_telemetry;
 mod event;
 pub(crate) mod header;
 mod lap_data;
-mod motion;
+pub(crate) mod motion;
 mod participants;
 mod session;
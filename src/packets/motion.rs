@@ -11,7 +11,7 @@ type WheelAccelerations = WheelData<f32>;
 type WheelSpeeds = WheelData<f32>;
 type WheelSlips = WheelData<f32>;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Coordinates<T>
 where
     T: Copy + Clone,
@@ -22,6 +22,12 @@ where
 }
 
 impl Coordinates<f32> {
+    /// Straight line distance to another point.
+    pub fn distance(&self, other: &Coordinates<f32>) -> f32 {
+        ((self.x - other.x).powi(2) + (self.y - other.y).powi(2) + (self.z - other.z).powi(2))
+            .sqrt()
+    }
+
     fn parse(input: &[u8]) -> ParseResult<Self> {
         map(tuple((le_f32, le_f32, le_f32)), |(x, y, z)| Coordinates {
             x,
@@ -118,7 +124,7 @@ impl CarMotionData {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MotionData {
     pub car_motion_data: Vec<CarMotionData>,
 
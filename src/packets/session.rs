@@ -23,27 +23,27 @@ impl MarshalZone {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SessionData {
-    weather: Weather,
-    track_temperature: i8,
-    air_temperature: i8,
-    total_laps: u8,
-    track_length: u16,
-    session_type: SessionType,
-    track_id: TrackId,
-    formula: Formula,
-    session_time_left: u16,
-    session_duration: u16,
-    pit_speed_limit: u8,
-    game_paused: u8,
-    is_spectating: u8,
-    spectator_car_index: u8,
-    sli_pro_native_support: u8,
-    num_marshal_zones: u8,
-    marshal_zones: Vec<MarshalZone>,
-    safety_car_status: SafetyCarStatus,
-    network_game: NetworkGame,
+    pub weather: Weather,
+    pub track_temperature: i8,
+    pub air_temperature: i8,
+    pub total_laps: u8,
+    pub track_length: u16,
+    pub session_type: SessionType,
+    pub track_id: TrackId,
+    pub formula: Formula,
+    pub session_time_left: u16,
+    pub session_duration: u16,
+    pub pit_speed_limit: u8,
+    pub game_paused: u8,
+    pub is_spectating: u8,
+    pub spectator_car_index: u8,
+    pub sli_pro_native_support: u8,
+    pub num_marshal_zones: u8,
+    pub marshal_zones: Vec<MarshalZone>,
+    pub safety_car_status: SafetyCarStatus,
+    pub network_game: NetworkGame,
 }
 
 impl SessionData {